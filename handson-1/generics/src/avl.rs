@@ -0,0 +1,453 @@
+use std::cmp::Ordering;
+
+use crate::Node;
+
+/// Ordered set kept balanced with AVL rotations.
+/// It uses the same arena layout of `Tree`: the nodes live in a `Vec` and the
+/// links are ids, with a free list for the removed slots. The height of each
+/// node is stored in `heights`, at the same index of the node.
+/// The height of the tree is O(log n), so every operation takes O(log n).
+pub struct AvlTree<T> {
+    nodes: Vec<Node<T>>,
+    // Height of the subtree rooted at each node, a leaf has height 1
+    heights: Vec<usize>,
+    root: Option<usize>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<T: Ord + Copy> AvlTree<T> {
+    /// Returns an empty set
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            heights: Vec::new(),
+            root: None,
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of keys in the set
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns if the set has no keys
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the height of the tree, 0 if it is empty
+    pub fn height(&self) -> usize {
+        self.height_of(self.root)
+    }
+
+    /// Returns if `key` is in the set
+    pub fn contains(&self, key: &T) -> bool {
+        let mut current_node = self.root;
+
+        while let Some(current_id) = current_node {
+            let node = &self.nodes[current_id];
+            current_node = match key.cmp(&node.key) {
+                Ordering::Equal => return true,
+                Ordering::Less => node.id_left,
+                Ordering::Greater => node.id_right,
+            };
+        }
+        false
+    }
+
+    /// Inserts `key` and returns if it was not in the set already
+    pub fn insert(&mut self, key: T) -> bool {
+        let (root, inserted) = self.rec_insert(self.root, key);
+        self.root = Some(root);
+        if inserted {
+            self.len += 1;
+        }
+        inserted
+    }
+
+    /// Removes `key` and returns if it was in the set
+    pub fn remove(&mut self, key: &T) -> bool {
+        let (root, removed) = self.rec_remove(self.root, key);
+        self.root = root;
+        if removed {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Returns the keys in increasing order
+    pub fn to_vec(&self) -> Vec<T> {
+        let mut keys = Vec::with_capacity(self.len);
+        let mut stack = Vec::new();
+        let mut current_node = self.root;
+
+        loop {
+            while let Some(current_id) = current_node {
+                stack.push(current_id);
+                current_node = self.nodes[current_id].id_left;
+            }
+            let Some(current_id) = stack.pop() else {
+                return keys;
+            };
+            keys.push(self.nodes[current_id].key);
+            current_node = self.nodes[current_id].id_right;
+        }
+    }
+
+    /// Checks the invariants of the tree and returns a description of the first
+    /// violation found:
+    /// - the keys are strictly increasing in-order
+    /// - the stored heights are the real heights of the subtrees
+    /// - the heights of the children of every node differ at most by one
+    /// - the number of nodes is `len()`
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let mut count = 0;
+        self.rec_check(self.root, None, None, &mut count)?;
+
+        if count != self.len {
+            return Err(format!("Found {} nodes, but len is {}", count, self.len));
+        }
+        Ok(())
+    }
+
+    /// Auxiliary function of `check_invariants`. The keys of the subtree rooted at
+    /// `current_node` must be strictly between `lower` and `upper`.
+    /// It returns the computed height of the subtree.
+    fn rec_check(
+        &self,
+        current_node: Option<usize>,
+        lower: Option<T>,
+        upper: Option<T>,
+        count: &mut usize,
+    ) -> Result<usize, String> {
+        let Some(current_id) = current_node else {
+            return Ok(0);
+        };
+        let node = &self.nodes[current_id];
+        *count += 1;
+
+        if lower.is_some_and(|lower| lower >= node.key)
+            || upper.is_some_and(|upper| upper <= node.key)
+        {
+            return Err(format!("Node {} breaks the key order", current_id));
+        }
+
+        let left_height = self.rec_check(node.id_left, lower, Some(node.key), count)?;
+        let right_height = self.rec_check(node.id_right, Some(node.key), upper, count)?;
+
+        if left_height.abs_diff(right_height) > 1 {
+            return Err(format!("Node {} is not balanced", current_id));
+        }
+        let height = 1 + left_height.max(right_height);
+        if self.heights[current_id] != height {
+            return Err(format!(
+                "Node {} stores height {}, but it is {}",
+                current_id, self.heights[current_id], height
+            ));
+        }
+        Ok(height)
+    }
+
+    /// Inserts `key` in the subtree rooted at `current_node`.
+    /// It returns the new root of the subtree and if the key has been inserted.
+    fn rec_insert(&mut self, current_node: Option<usize>, key: T) -> (usize, bool) {
+        let Some(current_id) = current_node else {
+            return (self.alloc(key), true);
+        };
+
+        let inserted = match key.cmp(&self.nodes[current_id].key) {
+            Ordering::Equal => return (current_id, false),
+            Ordering::Less => {
+                let (left, inserted) = self.rec_insert(self.nodes[current_id].id_left, key);
+                self.nodes[current_id].id_left = Some(left);
+                inserted
+            }
+            Ordering::Greater => {
+                let (right, inserted) = self.rec_insert(self.nodes[current_id].id_right, key);
+                self.nodes[current_id].id_right = Some(right);
+                inserted
+            }
+        };
+
+        (self.rebalance(current_id), inserted)
+    }
+
+    /// Removes `key` from the subtree rooted at `current_node`.
+    /// It returns the new root of the subtree and if the key has been removed.
+    fn rec_remove(&mut self, current_node: Option<usize>, key: &T) -> (Option<usize>, bool) {
+        let Some(current_id) = current_node else {
+            return (None, false);
+        };
+        let node = &self.nodes[current_id];
+
+        match key.cmp(&node.key) {
+            Ordering::Less => {
+                let (left, removed) = self.rec_remove(node.id_left, key);
+                self.nodes[current_id].id_left = left;
+                (Some(self.rebalance(current_id)), removed)
+            }
+            Ordering::Greater => {
+                let (right, removed) = self.rec_remove(node.id_right, key);
+                self.nodes[current_id].id_right = right;
+                (Some(self.rebalance(current_id)), removed)
+            }
+            Ordering::Equal => match (node.id_left, node.id_right) {
+                (Some(_), Some(right_id)) => {
+                    // Take the key of the successor and remove it from the right subtree
+                    let (right, successor_key) = self.remove_min(right_id);
+                    self.nodes[current_id].key = successor_key;
+                    self.nodes[current_id].id_right = right;
+                    (Some(self.rebalance(current_id)), true)
+                }
+                (child, None) | (None, child) => {
+                    self.release(current_id);
+                    (child, true)
+                }
+            },
+        }
+    }
+
+    /// Removes the smallest key of the subtree rooted at `current_id`.
+    /// It returns the new root of the subtree and the removed key.
+    fn remove_min(&mut self, current_id: usize) -> (Option<usize>, T) {
+        let node = &self.nodes[current_id];
+        match node.id_left {
+            Some(left_id) => {
+                let (left, key) = self.remove_min(left_id);
+                self.nodes[current_id].id_left = left;
+                (Some(self.rebalance(current_id)), key)
+            }
+            None => {
+                let (right, key) = (node.id_right, node.key);
+                self.release(current_id);
+                (right, key)
+            }
+        }
+    }
+
+    /// Updates the height of `current_id` and, if the heights of its children differ
+    /// by two, restores the balance with one or two rotations.
+    /// It returns the new root of the subtree.
+    fn rebalance(&mut self, current_id: usize) -> usize {
+        self.update_height(current_id);
+        let node = &self.nodes[current_id];
+
+        match self.balance_factor(current_id) {
+            // Left-heavy
+            2 => {
+                let left_id = node.id_left.unwrap();
+                // Left-right case: the left child becomes left-heavy first
+                if self.balance_factor(left_id) < 0 {
+                    self.nodes[current_id].id_left = Some(self.rotate_left(left_id));
+                }
+                self.rotate_right(current_id)
+            }
+            // Right-heavy
+            -2 => {
+                let right_id = node.id_right.unwrap();
+                // Right-left case: the right child becomes right-heavy first
+                if self.balance_factor(right_id) > 0 {
+                    self.nodes[current_id].id_right = Some(self.rotate_right(right_id));
+                }
+                self.rotate_left(current_id)
+            }
+            _ => current_id,
+        }
+    }
+
+    //     x            y
+    //    / \          / \
+    //   a   y   ->   x   c
+    //      / \      / \
+    //     b   c    a   b
+    fn rotate_left(&mut self, x: usize) -> usize {
+        let y = self.nodes[x]
+            .id_right
+            .expect("Rotation without right child");
+        self.nodes[x].id_right = self.nodes[y].id_left;
+        self.nodes[y].id_left = Some(x);
+        self.update_height(x);
+        self.update_height(y);
+        y
+    }
+
+    //       y          x
+    //      / \        / \
+    //     x   c  ->  a   y
+    //    / \            / \
+    //   a   b          b   c
+    fn rotate_right(&mut self, y: usize) -> usize {
+        let x = self.nodes[y].id_left.expect("Rotation without left child");
+        self.nodes[y].id_left = self.nodes[x].id_right;
+        self.nodes[x].id_right = Some(y);
+        self.update_height(y);
+        self.update_height(x);
+        x
+    }
+
+    fn height_of(&self, node_id: Option<usize>) -> usize {
+        node_id.map_or(0, |id| self.heights[id])
+    }
+
+    fn update_height(&mut self, node_id: usize) {
+        let node = &self.nodes[node_id];
+        self.heights[node_id] = 1 + self
+            .height_of(node.id_left)
+            .max(self.height_of(node.id_right));
+    }
+
+    /// Height of the left subtree minus height of the right subtree
+    fn balance_factor(&self, node_id: usize) -> isize {
+        let node = &self.nodes[node_id];
+        self.height_of(node.id_left) as isize - self.height_of(node.id_right) as isize
+    }
+
+    /// Stores a new leaf with the given `key`, reusing a reclaimed slot if there is one.
+    fn alloc(&mut self, key: T) -> usize {
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = Node::new(key);
+                self.heights[id] = 1;
+                id
+            }
+            None => {
+                self.nodes.push(Node::new(key));
+                self.heights.push(1);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Puts the slot of `node_id` in the free list. The node must be already unlinked.
+    fn release(&mut self, node_id: usize) {
+        let node = &mut self.nodes[node_id];
+        node.id_left = None;
+        node.id_right = None;
+        node.alive = false;
+        self.free.push(node_id);
+    }
+}
+
+impl<T: Ord + Copy> Default for AvlTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::TreeGenerator;
+
+    /// Maximum height of an AVL tree with `n` nodes: 1.44 * log2(n + 2)
+    fn max_height(n: usize) -> usize {
+        (1.4405 * ((n + 2) as f64).log2()) as usize
+    }
+
+    #[test]
+    fn test_insert_rotations() {
+        // Right-right, left-left, left-right and right-left cases
+        for keys in [[1, 2, 3], [3, 2, 1], [3, 1, 2], [1, 3, 2]] {
+            let mut set = AvlTree::new();
+            for key in keys {
+                assert!(set.insert(key));
+            }
+            assert_eq!(set.check_invariants(), Ok(()));
+            assert_eq!(set.height(), 2);
+            assert_eq!(set.nodes[set.root.unwrap()].key, 2);
+        }
+    }
+
+    #[test]
+    fn test_insert_duplicates() {
+        let mut set = AvlTree::new();
+        assert!(set.insert(5));
+        assert!(!set.insert(5));
+        assert_eq!(set.len(), 1);
+        assert!(set.contains(&5));
+        assert!(!set.contains(&4));
+    }
+
+    #[test]
+    fn test_sorted_insertions() {
+        // A plain BST would degenerate into a chain
+        let n = 100_000;
+        let mut set = AvlTree::new();
+        for key in 0..n {
+            set.insert(key);
+        }
+
+        assert_eq!(set.check_invariants(), Ok(()));
+        assert!(set.height() <= max_height(n));
+        assert_eq!(set.to_vec(), (0..n).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut set = AvlTree::new();
+        for key in 0..100 {
+            set.insert(key);
+        }
+        for key in (0..100).filter(|key| key % 3 != 0) {
+            assert!(set.remove(&key));
+            assert_eq!(set.check_invariants(), Ok(()));
+        }
+
+        assert!(!set.remove(&1));
+        assert_eq!(set.len(), 34);
+        assert_eq!(
+            set.to_vec(),
+            (0..100).filter(|key| key % 3 == 0).collect::<Vec<_>>()
+        );
+
+        for key in (0..100).step_by(3) {
+            assert!(set.remove(&key));
+        }
+        assert!(set.is_empty());
+        assert_eq!(set.height(), 0);
+    }
+
+    #[test]
+    fn test_random_operations() {
+        // Compare with a BTreeSet
+        let mut generator = TreeGenerator::new(7);
+
+        let mut set = AvlTree::new();
+        let mut oracle = BTreeSet::new();
+
+        for _ in 0..20_000 {
+            let key = generator.key(&(0..=499));
+            if generator.below(2) == 0 {
+                assert_eq!(set.insert(key), oracle.insert(key));
+            } else {
+                assert_eq!(set.remove(&key), oracle.remove(&key));
+            }
+
+            assert_eq!(set.check_invariants(), Ok(()));
+            assert!(set.height() <= max_height(set.len()));
+            assert_eq!(set.len(), oracle.len());
+        }
+        assert_eq!(set.to_vec(), oracle.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_slots_are_reused() {
+        let mut set = AvlTree::new();
+        for key in 0..10 {
+            set.insert(key);
+        }
+        for key in 0..10 {
+            set.remove(&key);
+        }
+        for key in 10..20 {
+            set.insert(key);
+        }
+        assert_eq!(set.nodes.len(), 10);
+        assert_eq!(set.check_invariants(), Ok(()));
+    }
+}
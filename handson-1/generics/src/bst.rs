@@ -0,0 +1,368 @@
+use std::{cmp::Ordering, ops::Add};
+
+use crate::Tree;
+
+/// Where `Tree::insert` puts a key that is already in the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// The key is not inserted
+    Reject,
+    /// The key goes in the left subtree of the equal key
+    Left,
+    /// The key goes in the right subtree of the equal key
+    Right,
+}
+
+/// Binary search tree operations. They keep the BST property, so `is_bst()` holds
+/// for every tree built only with `new`, `with_root`, `insert` and `remove`.
+/// On a tree that is not a BST the results are unspecified.
+/// Every operation walks a single root-to-leaf path, so it takes O(height).
+impl<T: Ord + Add<Output = T> + Default + Copy> Tree<T> {
+    /// Sets how `insert` handles keys that are already in the tree.
+    /// The default is `DuplicatePolicy::Reject`.
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicates = policy;
+    }
+
+    /// Inserts `key` in key order and returns the id of the new node.
+    /// It returns None if the key is already in the tree and the policy is `Reject`.
+    pub fn insert(&mut self, key: T) -> Option<usize> {
+        let Some(mut current_id) = self.root else {
+            let id = self.alloc(key);
+            self.root = Some(id);
+            return Some(id);
+        };
+
+        loop {
+            let node = &self.nodes[current_id];
+            let is_left = match key.cmp(&node.key) {
+                Ordering::Less => true,
+                Ordering::Greater => false,
+                Ordering::Equal => match self.duplicates {
+                    DuplicatePolicy::Reject => return None,
+                    DuplicatePolicy::Left => true,
+                    DuplicatePolicy::Right => false,
+                },
+            };
+
+            let child = if is_left { node.id_left } else { node.id_right };
+            match child {
+                Some(child_id) => current_id = child_id,
+                None => {
+                    let id = self.attach(current_id, key, is_left);
+                    self.update_ancestor_sizes(Some(current_id), |size| size + 1);
+                    return Some(id);
+                }
+            }
+        }
+    }
+
+    /// Returns if `key` is in the tree
+    pub fn contains(&self, key: &T) -> bool {
+        self.find(key).is_some()
+    }
+
+    /// Removes one node with `key` and returns if it was found.
+    /// A node with two children takes the key of its successor,
+    /// then the node of the successor is removed.
+    pub fn remove(&mut self, key: &T) -> bool {
+        let Some((parent_id, node_id)) = self.find(key) else {
+            return false;
+        };
+
+        let node = &self.nodes[node_id];
+        match (node.id_left, node.id_right) {
+            (Some(_), Some(right_id)) => {
+                // The successor is the leftmost node of the right subtree,
+                // so it has no left child
+                let (mut successor_parent, mut successor) = (node_id, right_id);
+                while let Some(left_id) = self.nodes[successor].id_left {
+                    (successor_parent, successor) = (successor, left_id);
+                }
+
+                self.nodes[node_id].key = self.nodes[successor].key;
+                let successor_right = self.nodes[successor].id_right;
+                self.replace_child(Some(successor_parent), successor, successor_right);
+                self.release(successor);
+                self.update_ancestor_sizes(Some(successor_parent), |size| size - 1);
+            }
+            (child, None) | (None, child) => {
+                self.replace_child(parent_id, node_id, child);
+                self.release(node_id);
+                self.update_ancestor_sizes(parent_id, |size| size - 1);
+            }
+        }
+        true
+    }
+
+    /// Returns the smallest key, None if the tree is empty
+    pub fn min(&self) -> Option<T> {
+        let mut current_id = self.root?;
+        while let Some(left_id) = self.nodes[current_id].id_left {
+            current_id = left_id;
+        }
+        Some(self.nodes[current_id].key)
+    }
+
+    /// Returns the largest key, None if the tree is empty
+    pub fn max(&self) -> Option<T> {
+        let mut current_id = self.root?;
+        while let Some(right_id) = self.nodes[current_id].id_right {
+            current_id = right_id;
+        }
+        Some(self.nodes[current_id].key)
+    }
+
+    /// Returns the smallest key strictly greater than `key`.
+    /// `key` doesn't need to be in the tree.
+    pub fn successor(&self, key: &T) -> Option<T> {
+        let mut successor = None;
+        let mut current_node = self.root;
+
+        while let Some(current_id) = current_node {
+            let node = &self.nodes[current_id];
+            if node.key > *key {
+                // The node is a candidate, a closer one can only be on the left
+                successor = Some(node.key);
+                current_node = node.id_left;
+            } else {
+                current_node = node.id_right;
+            }
+        }
+        successor
+    }
+
+    /// Returns the largest key strictly smaller than `key`.
+    /// `key` doesn't need to be in the tree.
+    pub fn predecessor(&self, key: &T) -> Option<T> {
+        let mut predecessor = None;
+        let mut current_node = self.root;
+
+        while let Some(current_id) = current_node {
+            let node = &self.nodes[current_id];
+            if node.key < *key {
+                // The node is a candidate, a closer one can only be on the right
+                predecessor = Some(node.key);
+                current_node = node.id_right;
+            } else {
+                current_node = node.id_left;
+            }
+        }
+        predecessor
+    }
+
+    /// Returns the pair (parent id, node id) of the first node with `key`
+    /// on the path from the root.
+    fn find(&self, key: &T) -> Option<(Option<usize>, usize)> {
+        let mut parent_id = None;
+        let mut current_id = self.root?;
+
+        loop {
+            let node = &self.nodes[current_id];
+            let child = match key.cmp(&node.key) {
+                Ordering::Equal => return Some((parent_id, current_id)),
+                Ordering::Less => node.id_left,
+                Ordering::Greater => node.id_right,
+            };
+            parent_id = Some(current_id);
+            current_id = child?;
+        }
+    }
+
+    /// Replaces the link from `parent_id` (or from the root, if None) to `old_id`
+    /// with a link to `new_id`, and makes `parent_id` the parent of `new_id`.
+    fn replace_child(&mut self, parent_id: Option<usize>, old_id: usize, new_id: Option<usize>) {
+        match parent_id {
+            None => self.root = new_id,
+            Some(parent_id) => {
+                let parent = &mut self.nodes[parent_id];
+                if parent.id_left == Some(old_id) {
+                    parent.id_left = new_id;
+                } else {
+                    parent.id_right = new_id;
+                }
+            }
+        }
+        if let Some(new_id) = new_id {
+            self.nodes[new_id].id_parent = parent_id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::TreeGenerator;
+
+    fn bst(keys: &[i32]) -> Tree<i32> {
+        let mut tree = Tree::new();
+        for &key in keys {
+            tree.insert(key);
+        }
+        tree
+    }
+
+    fn inorder(tree: &Tree<i32>) -> Vec<i32> {
+        tree.iter_inorder().map(|(_, &key)| key).collect()
+    }
+
+    #[test]
+    fn test_insert() {
+        let tree = bst(&[40, 30, 50, 25, 35, 45, 60]);
+
+        //       40
+        //     /    \
+        //   30      50
+        //  / \     / \
+        // 25 35  45  60
+
+        assert!(tree.is_bst());
+        assert_eq!(tree.to_level_order(), "[40,30,50,25,35,45,60]");
+        assert_eq!(tree.sum(), 285);
+    }
+
+    #[test]
+    fn test_contains() {
+        let tree = bst(&[40, 30, 50, 25, 35, 45, 60]);
+
+        assert!(tree.contains(&40));
+        assert!(tree.contains(&25));
+        assert!(tree.contains(&60));
+        assert!(!tree.contains(&41));
+        assert!(!Tree::new().contains(&41));
+    }
+
+    #[test]
+    fn test_duplicate_policy() {
+        let mut tree = bst(&[10, 5, 20]);
+        assert_eq!(tree.insert(10), None);
+        assert_eq!(inorder(&tree), vec![5, 10, 20]);
+
+        tree.set_duplicate_policy(DuplicatePolicy::Left);
+        tree.insert(10);
+        assert_eq!(tree.to_level_order(), "[10,5,20,null,10]");
+        assert!(tree.is_bst());
+
+        tree.set_duplicate_policy(DuplicatePolicy::Right);
+        tree.insert(20);
+        assert_eq!(tree.to_level_order(), "[10,5,20,null,10,null,20]");
+        assert!(tree.is_bst());
+        assert_eq!(inorder(&tree), vec![5, 10, 10, 20, 20]);
+
+        // One occurrence at a time is removed
+        assert!(tree.remove(&10));
+        assert_eq!(inorder(&tree), vec![5, 10, 20, 20]);
+        assert!(tree.remove(&10));
+        assert!(!tree.remove(&10));
+        assert_eq!(inorder(&tree), vec![5, 20, 20]);
+        assert!(tree.is_bst());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tree = bst(&[40, 30, 50, 25, 35, 45, 60]);
+
+        // Leaf
+        assert!(tree.remove(&25));
+        assert_eq!(tree.to_level_order(), "[40,30,50,null,35,45,60]");
+
+        // One child
+        assert!(tree.remove(&30));
+        assert_eq!(tree.to_level_order(), "[40,35,50,null,null,45,60]");
+
+        // Two children, the root takes the key of its successor
+        assert!(tree.remove(&40));
+        assert_eq!(tree.to_level_order(), "[45,35,50,null,null,null,60]");
+        assert!(tree.is_bst());
+
+        assert!(!tree.remove(&40));
+        for key in [45, 35, 50, 60] {
+            assert!(tree.remove(&key));
+        }
+        assert_eq!(tree.to_level_order(), "[]");
+        assert_eq!(tree.min(), None);
+
+        // The tree can be filled again
+        tree.insert(1);
+        assert_eq!(tree.to_level_order(), "[1]");
+    }
+
+    #[test]
+    fn test_min_max() {
+        let tree = bst(&[40, 30, 50, 25, 35, 45, 60]);
+        assert_eq!(tree.min(), Some(25));
+        assert_eq!(tree.max(), Some(60));
+        assert_eq!(Tree::<i32>::new().max(), None);
+    }
+
+    #[test]
+    fn test_successor_predecessor() {
+        let tree = bst(&[40, 30, 50, 25, 35, 45, 60]);
+
+        assert_eq!(tree.successor(&35), Some(40));
+        assert_eq!(tree.successor(&40), Some(45));
+        assert_eq!(tree.successor(&41), Some(45));
+        assert_eq!(tree.successor(&60), None);
+        assert_eq!(tree.successor(&0), Some(25));
+
+        assert_eq!(tree.predecessor(&45), Some(40));
+        assert_eq!(tree.predecessor(&40), Some(35));
+        assert_eq!(tree.predecessor(&26), Some(25));
+        assert_eq!(tree.predecessor(&25), None);
+        assert_eq!(tree.predecessor(&100), Some(60));
+    }
+
+    #[test]
+    fn test_random_operations() {
+        // Compare with a BTreeMap of counters
+        let mut generator = TreeGenerator::new(42);
+
+        for policy in [
+            DuplicatePolicy::Reject,
+            DuplicatePolicy::Left,
+            DuplicatePolicy::Right,
+        ] {
+            let mut tree = Tree::new();
+            tree.set_duplicate_policy(policy);
+            let mut oracle: BTreeMap<i32, usize> = BTreeMap::new();
+
+            for _ in 0..2000 {
+                let key = generator.key(&(0..=49));
+                if generator.below(3) == 0 {
+                    let removed = tree.remove(&key);
+                    let count = oracle.entry(key).or_default();
+                    assert_eq!(removed, *count > 0);
+                    *count = count.saturating_sub(1);
+                } else {
+                    let inserted = tree.insert(key).is_some();
+                    let count = oracle.entry(key).or_default();
+                    assert_eq!(inserted, policy != DuplicatePolicy::Reject || *count == 0);
+                    if inserted {
+                        *count += 1;
+                    }
+                }
+                oracle.retain(|_, count| *count > 0);
+
+                assert!(tree.is_bst());
+                assert_eq!(tree.min(), oracle.keys().next().copied());
+                assert_eq!(tree.max(), oracle.keys().next_back().copied());
+                assert_eq!(
+                    tree.successor(&key),
+                    oracle.range(key + 1..).next().map(|(k, _)| *k)
+                );
+                assert_eq!(
+                    tree.predecessor(&key),
+                    oracle.range(..key).next_back().map(|(k, _)| *k)
+                );
+            }
+
+            let expected: Vec<i32> = oracle
+                .iter()
+                .flat_map(|(&key, &count)| std::iter::repeat_n(key, count))
+                .collect();
+            assert_eq!(inorder(&tree), expected);
+        }
+    }
+}
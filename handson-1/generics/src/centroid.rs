@@ -0,0 +1,475 @@
+use std::ops::Add;
+
+use crate::{Tree, TreeError};
+
+/// Centroid decomposition of a tree, seen as an undirected graph of its
+/// parent/child links.
+/// The centroid of a component is the node whose removal leaves pieces of at most
+/// half the size, the centroids of the pieces are its children in the centroid tree.
+/// The centroid tree has height O(log n), and the path between two nodes always goes
+/// through their lowest common ancestor in the centroid tree.
+/// Building it takes O(n log n). The index is a snapshot: it must be rebuilt after
+/// the tree changes.
+pub struct CentroidTree {
+    // Centroid parent of every node id, None for the top centroid
+    parent: Vec<Option<usize>>,
+    // Depth in the centroid tree of every node id, None for the ids that are not in the tree
+    level: Vec<Option<usize>>,
+    root: Option<usize>,
+    // Distances from every node to its centroid ancestors, indexed by their level
+    distances: Vec<Vec<usize>>,
+    // Distance from every centroid to the nearest marked node of its component
+    nearest: Vec<Option<usize>>,
+}
+
+impl CentroidTree {
+    /// Builds the decomposition of `tree` in O(n log n)
+    pub fn new<T>(tree: &Tree<T>) -> Self {
+        let n = tree.nodes.len();
+        let mut centroids = CentroidTree {
+            parent: vec![None; n],
+            level: vec![None; n],
+            root: None,
+            distances: vec![Vec::new(); n],
+            nearest: vec![None; n],
+        };
+
+        // Components to decompose, as (any node of the component, centroid parent)
+        let mut stack: Vec<(usize, Option<usize>)> =
+            tree.root.map(|root| (root, None)).into_iter().collect();
+        // Size of the subtree of every node, in the traversal of its component,
+        // the node it was reached from and its distance from the centroid
+        let mut size = vec![0; n];
+        let mut from = vec![None; n];
+        let mut distance = vec![0; n];
+
+        while let Some((start, parent)) = stack.pop() {
+            let level = parent.map_or(0, |parent| centroids.level[parent].unwrap() + 1);
+            let order = centroids.component(tree, start, level, &mut from);
+
+            // Sizes from the leaves of the traversal, then the centroid is the first node
+            // from the start whose heaviest piece is at most half of the component
+            for &id in order.iter().rev() {
+                size[id] = 1 + centroids
+                    .neighbours(tree, id, level)
+                    .filter(|&next| Some(next) != from[id])
+                    .map(|next| size[next])
+                    .sum::<usize>();
+            }
+            let total = order.len();
+            let mut centroid = start;
+            loop {
+                let heaviest = centroids
+                    .neighbours(tree, centroid, level)
+                    .filter(|&next| Some(next) != from[centroid])
+                    .max_by_key(|&next| size[next]);
+                match heaviest {
+                    Some(next) if 2 * size[next] > total => centroid = next,
+                    _ => break,
+                }
+            }
+
+            centroids.parent[centroid] = parent;
+            centroids.level[centroid] = Some(level);
+            if parent.is_none() {
+                centroids.root = Some(centroid);
+            }
+
+            // Distances from the centroid to its component, in the traversal from it
+            for id in centroids.component(tree, centroid, level + 1, &mut from) {
+                distance[id] = from[id].map_or(0, |previous| distance[previous] + 1);
+                centroids.distances[id].push(distance[id]);
+            }
+
+            stack.extend(
+                centroids
+                    .neighbours(tree, centroid, level + 1)
+                    .map(|next| (next, Some(centroid))),
+            );
+        }
+        centroids
+    }
+
+    /// Returns the top centroid, None if the tree is empty
+    pub fn root(&self) -> Option<usize> {
+        self.root
+    }
+
+    /// Returns the parent of `u` in the centroid tree, None for the top centroid
+    ///
+    /// # Panics
+    /// Panics if `u` is not in the tree.
+    pub fn parent(&self, u: usize) -> Option<usize> {
+        self.try_parent(u).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the parent of `u` like `parent`, or an error if `u` is not in the tree
+    pub fn try_parent(&self, u: usize) -> Result<Option<usize>, TreeError> {
+        self.try_level(u)?;
+        Ok(self.parent[u])
+    }
+
+    /// Returns the depth of `u` in the centroid tree, the top centroid has depth 0.
+    /// It is at most log2(n).
+    ///
+    /// # Panics
+    /// Panics if `u` is not in the tree.
+    pub fn depth(&self, u: usize) -> usize {
+        self.try_level(u).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Marks `u`, so that it is found by `nearest_marked`. It takes O(log n).
+    ///
+    /// # Panics
+    /// Panics if `u` is not in the tree.
+    pub fn mark(&mut self, u: usize) {
+        self.try_level(u).unwrap_or_else(|err| panic!("{}", err));
+        let mut current = Some(u);
+        while let Some(centroid) = current {
+            let distance = self.distance_to_ancestor(u, centroid);
+            let nearest = &mut self.nearest[centroid];
+            if nearest.is_none_or(|nearest| distance < nearest) {
+                *nearest = Some(distance);
+            }
+            current = self.parent[centroid];
+        }
+    }
+
+    /// Returns the number of edges between `u` and the nearest marked node,
+    /// None if no node is marked. It takes O(log n).
+    ///
+    /// # Panics
+    /// Panics if `u` is not in the tree.
+    pub fn nearest_marked(&self, u: usize) -> Option<usize> {
+        self.try_level(u).unwrap_or_else(|err| panic!("{}", err));
+        // The path to the nearest marked node goes through a centroid ancestor of `u`
+        std::iter::successors(Some(u), |&centroid| self.parent[centroid])
+            .filter_map(|centroid| {
+                self.nearest[centroid]
+                    .map(|nearest| nearest + self.distance_to_ancestor(u, centroid))
+            })
+            .min()
+    }
+
+    /// Returns the number of paths whose keys sum to `k`, the path of a single
+    /// node included. A path is a set of nodes, so it is counted once in either
+    /// direction. `tree` must be the tree the index was built from.
+    /// Every centroid counts the paths through it sorting the sums of their halves,
+    /// it takes O(n log^2 n). The keys are only added and compared, so unsigned keys work too.
+    pub fn count_paths_with_sum<T>(&self, tree: &Tree<T>, k: T) -> usize
+    where
+        T: Copy + Add<Output = T> + Ord,
+    {
+        let mut count = 0;
+
+        for (centroid, level) in self.level.iter().enumerate() {
+            let Some(level) = *level else {
+                continue;
+            };
+            // The sum of a path through the centroid is key + the sums of its two
+            // halves, each one a path that starts next to the centroid
+            let key = tree.nodes[centroid].key;
+            if key == k {
+                count += 1;
+            }
+
+            // Sums of the halves in all the pieces, and the pairs of halves in the
+            // same piece, that are not paths
+            let mut halves = vec![];
+            let mut same_piece = 0;
+            for start in self.neighbours(tree, centroid, level + 1) {
+                // Sums of the paths from every node of the piece up to the centroid excluded
+                let mut sums = vec![];
+                let mut stack = vec![(start, centroid, tree.nodes[start].key)];
+                while let Some((id, from, sum)) = stack.pop() {
+                    sums.push(sum);
+                    for next in self
+                        .neighbours(tree, id, level + 1)
+                        .filter(|&next| next != from)
+                    {
+                        stack.push((next, id, sum + tree.nodes[next].key));
+                    }
+                }
+
+                same_piece += count_pairs(&mut sums, key, k);
+                halves.extend(sums);
+            }
+
+            // Paths with the centroid as one end, then the ones with an end in two pieces
+            count += halves.iter().filter(|&&sum| key + sum == k).count();
+            count += count_pairs(&mut halves, key, k) - same_piece;
+        }
+        count
+    }
+
+    /// Returns the distance between `u` and its centroid ancestor `centroid`
+    fn distance_to_ancestor(&self, u: usize, centroid: usize) -> usize {
+        self.distances[u][self.level[centroid].unwrap()]
+    }
+
+    /// Returns the neighbours of `id` that are still in the components of depth `level`,
+    /// the ones that are not centroids of a lower depth
+    fn neighbours<'a, T>(
+        &'a self,
+        tree: &'a Tree<T>,
+        id: usize,
+        level: usize,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let node = &tree.nodes[id];
+        node.id_parent
+            .into_iter()
+            .chain(tree.children(id))
+            .filter(move |&next| self.level[next].is_none_or(|next_level| next_level >= level))
+    }
+
+    /// Returns the nodes of the component of depth `level` that contains `start`,
+    /// in the order of a visit from `start`. It sets in `from` the node each one
+    /// was reached from.
+    fn component<T>(
+        &self,
+        tree: &Tree<T>,
+        start: usize,
+        level: usize,
+        from: &mut [Option<usize>],
+    ) -> Vec<usize> {
+        let mut order = vec![];
+        from[start] = None;
+        let mut stack = vec![start];
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            for next in self.neighbours(tree, id, level) {
+                if Some(next) != from[id] {
+                    from[next] = Some(id);
+                    stack.push(next);
+                }
+            }
+        }
+        order
+    }
+
+    fn try_level(&self, u: usize) -> Result<usize, TreeError> {
+        self.level
+            .get(u)
+            .copied()
+            .flatten()
+            .ok_or(TreeError::NodeNotFound { node_id: u })
+    }
+}
+
+/// Returns the number of pairs of `sums` with `key + a + b == k`, sorting them
+/// and moving two pointers towards each other
+fn count_pairs<T: Copy + Add<Output = T> + Ord>(sums: &mut [T], key: T, k: T) -> usize {
+    sums.sort_unstable();
+    let mut count = 0;
+    let (mut low, mut high) = (0, sums.len());
+    while low + 1 < high {
+        let (a, b) = (sums[low], sums[high - 1]);
+        if key + a + b < k {
+            low += 1;
+        } else if key + a + b > k {
+            high -= 1;
+        } else if a == b {
+            // All the sums in between are equal, every pair of them counts
+            let equal = high - low;
+            count += equal * (equal - 1) / 2;
+            break;
+        } else {
+            let lows = sums[low..high].iter().take_while(|&&sum| sum == a).count();
+            let highs = sums[low..high]
+                .iter()
+                .rev()
+                .take_while(|&&sum| sum == b)
+                .count();
+            count += lows * highs;
+            low += lows;
+            high -= highs;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LcaIndex, Shape, TreeGenerator};
+
+    fn tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 2, true); // id 1
+        tree.add_node(0, 3, false); // id 2
+        tree.add_node(1, -1, true); // id 3
+        tree.add_node(1, 4, false); // id 4
+        tree.add_node(4, 1, true); // id 5
+        tree.add_node(2, 2, false); // id 6
+
+        //        1
+        //      /   \
+        //     2     3
+        //    / \     \
+        //  -1   4     2
+        //      /
+        //     1
+        tree
+    }
+
+    /// Sum of the keys on the path between `u` and `v`
+    fn path_sum(tree: &Tree<i32>, index: &LcaIndex, u: usize, v: usize) -> i32 {
+        let lca = index.lca(u, v);
+        let mut sum = tree.nodes[lca].key;
+        for mut id in [u, v] {
+            while id != lca {
+                sum += tree.nodes[id].key;
+                id = tree.nodes[id].id_parent.unwrap();
+            }
+        }
+        sum
+    }
+
+    fn brute_force_count(tree: &Tree<i32>, k: i32) -> usize {
+        let index = LcaIndex::new(tree);
+        let ids: Vec<usize> = tree.iter_preorder().map(|(id, _)| id).collect();
+        let mut count = 0;
+        for (i, &u) in ids.iter().enumerate() {
+            for &v in &ids[i..] {
+                if path_sum(tree, &index, u, v) == k {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_centroid_parents() {
+        let tree = tree();
+        let centroids = CentroidTree::new(&tree);
+
+        // Removing node 0 leaves pieces of 4 and 2 nodes, node 1 leaves 1, 2 and 3
+        assert_eq!(centroids.root(), Some(1));
+        assert_eq!(centroids.parent(1), None);
+        assert_eq!(centroids.depth(1), 0);
+        for id in 0..7 {
+            if id != 1 {
+                let parent = centroids.parent(id).unwrap();
+                assert!(centroids.depth(parent) < centroids.depth(id));
+            }
+        }
+        assert_eq!(centroids.parent(3), Some(1));
+        assert_eq!(centroids.parent(4), Some(1));
+        assert_eq!(centroids.parent(5), Some(4));
+    }
+
+    #[test]
+    fn test_count_paths_with_sum() {
+        let tree = tree();
+        let centroids = CentroidTree::new(&tree);
+
+        for k in -2..12 {
+            assert_eq!(
+                centroids.count_paths_with_sum(&tree, k),
+                brute_force_count(&tree, k)
+            );
+        }
+        // The single nodes 1 and 1, and the path -1 - 2
+        assert_eq!(centroids.count_paths_with_sum(&tree, 1), 3);
+    }
+
+    #[test]
+    fn test_count_paths_unsigned() {
+        let mut tree = Tree::with_root(5u32);
+        tree.add_node(0, 1, true);
+        let centroids = CentroidTree::new(&tree);
+        assert_eq!(centroids.count_paths_with_sum(&tree, 1), 1);
+        assert_eq!(centroids.count_paths_with_sum(&tree, 0), 0);
+        assert_eq!(centroids.count_paths_with_sum(&tree, 6), 1);
+
+        // The same seed gives the same trees with u32 and i32 keys
+        for n in 1..40 {
+            let unsigned = TreeGenerator::new(n as u64).tree(n, Shape::RandomBinary, 0u32..=9);
+            let signed = TreeGenerator::new(n as u64).tree(n, Shape::RandomBinary, 0i32..=9);
+            let centroids = CentroidTree::new(&unsigned);
+            for k in 0..20 {
+                assert_eq!(
+                    centroids.count_paths_with_sum(&unsigned, k),
+                    brute_force_count(&signed, k as i32)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearest_marked() {
+        let tree = tree();
+        let mut centroids = CentroidTree::new(&tree);
+        assert_eq!(centroids.nearest_marked(0), None);
+
+        centroids.mark(6);
+        assert_eq!(centroids.nearest_marked(6), Some(0));
+        assert_eq!(centroids.nearest_marked(5), Some(5));
+
+        centroids.mark(3);
+        assert_eq!(centroids.nearest_marked(5), Some(3));
+        assert_eq!(centroids.nearest_marked(2), Some(1));
+    }
+
+    #[test]
+    fn test_random_trees() {
+        let mut generator = TreeGenerator::new(10);
+        for n in 1..50 {
+            let shape = [Shape::RandomBinary, Shape::Chain, Shape::Complete][n % 3];
+            let tree = generator.tree(n, shape, -3i32..=3);
+            let index = LcaIndex::new(&tree);
+            let mut centroids = CentroidTree::new(&tree);
+
+            for k in -4..=4 {
+                assert_eq!(
+                    centroids.count_paths_with_sum(&tree, k),
+                    brute_force_count(&tree, k)
+                );
+            }
+
+            let mut marked = vec![];
+            for _ in 0..5 {
+                let id = generator.below(n);
+                centroids.mark(id);
+                marked.push(id);
+                for u in 0..n {
+                    let expected = marked.iter().map(|&v| index.distance(u, v)).min();
+                    assert_eq!(centroids.nearest_marked(u), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_removed_nodes() {
+        let mut tree = tree();
+        tree.remove_subtree(4);
+        let centroids = CentroidTree::new(&tree);
+
+        assert_eq!(
+            centroids.try_parent(5),
+            Err(TreeError::NodeNotFound { node_id: 5 })
+        );
+        assert_eq!(
+            centroids.count_paths_with_sum(&tree, 3),
+            brute_force_count(&tree, 3)
+        );
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let n = 100_000;
+        let tree = TreeGenerator::new(11).tree(n, Shape::Chain, 1i64..=1);
+        let mut centroids = CentroidTree::new(&tree);
+
+        let depth = (0..n).map(|id| centroids.depth(id)).max().unwrap();
+        assert!(depth <= 17);
+
+        // On a chain of ones there are n - k + 1 paths of sum k
+        assert_eq!(centroids.count_paths_with_sum(&tree, 1_000), n - 1_000 + 1);
+
+        centroids.mark(0);
+        assert_eq!(centroids.nearest_marked(n - 1), Some(n - 1));
+    }
+}
@@ -0,0 +1,332 @@
+use std::ops::Add;
+
+use crate::{Tree, TreeError};
+
+/// One difference between two trees, as returned by `Tree::diff`.
+/// A node is identified by its position, the path from the root with `true`
+/// for a step into the left child and `false` for a step into the right child.
+/// A subtree is stored as its keys in pre-order, with None for every missing child.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeEdit<T> {
+    /// A subtree was added at a position with no node
+    Insert {
+        path: Vec<bool>,
+        subtree: Vec<Option<T>>,
+    },
+    /// The subtree at the position was removed
+    Remove {
+        path: Vec<bool>,
+        subtree: Vec<Option<T>>,
+    },
+    /// The node at the position has a different key, its children are compared separately
+    ChangeKey { path: Vec<bool>, old: T, new: T },
+}
+
+impl<T: Ord + Add<Output = T> + Default + Copy> Tree<T> {
+    /// Returns the edits that turn `self` into `other`, in pre-order of their positions.
+    /// The nodes at the same position in both trees are matched, so a subtree that
+    /// moved to another position is removed and inserted again. It runs in O(n)
+    /// plus the length of the paths in the edits.
+    pub fn diff(&self, other: &Tree<T>) -> Vec<TreeEdit<T>> {
+        let mut edits = vec![];
+        let mut path = vec![];
+        // Positions to compare, as (node in self, node in other, depth of the parent, last step)
+        let mut stack = vec![(self.root, other.root, 0, None)];
+
+        while let Some((old, new, depth, step)) = stack.pop() {
+            path.truncate(depth);
+            path.extend(step);
+
+            match (old, new) {
+                (None, None) => {}
+                (Some(old), None) => edits.push(TreeEdit::Remove {
+                    path: path.clone(),
+                    subtree: self.encode(old),
+                }),
+                (None, Some(new)) => edits.push(TreeEdit::Insert {
+                    path: path.clone(),
+                    subtree: other.encode(new),
+                }),
+                (Some(old), Some(new)) => {
+                    let (old, new) = (&self.nodes[old], &other.nodes[new]);
+                    if old.key != new.key {
+                        edits.push(TreeEdit::ChangeKey {
+                            path: path.clone(),
+                            old: old.key,
+                            new: new.key,
+                        });
+                    }
+                    // Push the right children first to compare the left ones first
+                    stack.push((old.id_right, new.id_right, path.len(), Some(false)));
+                    stack.push((old.id_left, new.id_left, path.len(), Some(true)));
+                }
+            }
+        }
+        edits
+    }
+
+    /// Applies the edits returned by `diff`, in order.
+    ///
+    /// # Panics
+    /// Panics if an edit does not fit the tree. `try_apply` returns the error instead.
+    pub fn apply(&mut self, edits: &[TreeEdit<T>]) {
+        self.try_apply(edits)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Applies the edits like `apply`. It returns an error if a `Remove` or a
+    /// `ChangeKey` has no node at its position, or if an `Insert` has a node at its
+    /// position or no parent. The edits before the failing one stay applied.
+    /// The keys of the removed subtrees and the old keys are not checked.
+    pub fn try_apply(&mut self, edits: &[TreeEdit<T>]) -> Result<(), TreeError> {
+        for edit in edits {
+            match edit {
+                TreeEdit::Insert { path, subtree } => self.insert_at(path, subtree)?,
+                TreeEdit::Remove { path, .. } => {
+                    let id = self.find_path(path)?;
+                    self.remove_subtree(id);
+                }
+                TreeEdit::ChangeKey { path, new, .. } => {
+                    let id = self.find_path(path)?;
+                    self.nodes[id].key = *new;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the keys of the subtree of `node_id` in pre-order, with None for
+    /// every missing child
+    fn encode(&self, node_id: usize) -> Vec<Option<T>> {
+        let mut keys = vec![];
+        let mut stack = vec![Some(node_id)];
+        while let Some(current) = stack.pop() {
+            match current {
+                None => keys.push(None),
+                Some(id) => {
+                    let node = &self.nodes[id];
+                    keys.push(Some(node.key));
+                    stack.push(node.id_right);
+                    stack.push(node.id_left);
+                }
+            }
+        }
+        keys
+    }
+
+    /// Builds the subtree encoded in `subtree` at the empty position `path`.
+    /// The missing markers at the end are taken as None, so an empty
+    /// `subtree` inserts nothing.
+    fn insert_at(&mut self, path: &[bool], subtree: &[Option<T>]) -> Result<(), TreeError> {
+        let taken = TreeError::PathTaken { depth: path.len() };
+        // The parent of the new subtree and the side, None for the root
+        let slot = match path.split_last() {
+            None if self.root.is_some() => return Err(taken),
+            None => None,
+            Some((&is_left, parent_path)) => {
+                let parent_id = self.find_path(parent_path)?;
+                let parent = &self.nodes[parent_id];
+                let child = if is_left {
+                    parent.id_left
+                } else {
+                    parent.id_right
+                };
+                if child.is_some() {
+                    return Err(taken);
+                }
+                Some((parent_id, is_left))
+            }
+        };
+
+        // Slots still to fill in pre-order, the left one on top
+        let mut slots = vec![slot];
+        let mut subtree_root = None;
+        for &key in subtree {
+            let Some(slot) = slots.pop() else {
+                break;
+            };
+            let Some(key) = key else {
+                continue;
+            };
+            let id = match slot {
+                Some((parent_id, is_left)) => self.attach(parent_id, key, is_left),
+                None => {
+                    let id = self.alloc(key);
+                    self.root = Some(id);
+                    id
+                }
+            };
+            subtree_root.get_or_insert(id);
+            slots.push(Some((id, false)));
+            slots.push(Some((id, true)));
+        }
+        if let Some(root) = subtree_root {
+            self.count_attached(root);
+        }
+        Ok(())
+    }
+
+    /// Returns the id of the node at the end of `path`
+    fn find_path(&self, path: &[bool]) -> Result<usize, TreeError> {
+        let mut current = self.root;
+        for (depth, &is_left) in path.iter().enumerate() {
+            let node = &self.nodes[current.ok_or(TreeError::PathNotFound { depth })?];
+            current = if is_left { node.id_left } else { node.id_right };
+        }
+        current.ok_or(TreeError::PathNotFound { depth: path.len() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Shape, TreeGenerator};
+
+    fn tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 2, true); // id 1
+        tree.add_node(0, 3, false); // id 2
+        tree.add_node(1, 4, true); // id 3
+        tree.add_node(2, 5, false); // id 4
+
+        //        1
+        //      /   \
+        //     2     3
+        //    /       \
+        //   4         5
+        tree
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = tree();
+        let mut new = tree();
+        new.remove_subtree(3);
+        new.nodes[2].key = 6;
+        let id = new.add_node(4, 7, true);
+        new.add_node(id, 8, false);
+
+        //        1
+        //      /   \
+        //     2     6
+        //            \
+        //             5
+        //            /
+        //           7
+        //            \
+        //             8
+        let edits = old.diff(&new);
+        assert_eq!(
+            edits,
+            vec![
+                TreeEdit::Remove {
+                    path: vec![true, true],
+                    subtree: vec![Some(4), None, None]
+                },
+                TreeEdit::ChangeKey {
+                    path: vec![false],
+                    old: 3,
+                    new: 6
+                },
+                TreeEdit::Insert {
+                    path: vec![false, false, true],
+                    subtree: vec![Some(7), None, Some(8), None, None]
+                },
+            ]
+        );
+
+        let mut patched = tree();
+        patched.apply(&edits);
+        assert_eq!(patched.to_level_order(), new.to_level_order());
+        assert!(patched.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn test_diff_empty() {
+        let empty = Tree::<i32>::new();
+        assert!(empty.diff(&Tree::new()).is_empty());
+
+        let edits = empty.diff(&tree());
+        assert_eq!(edits.len(), 1);
+        let mut patched = Tree::new();
+        patched.apply(&edits);
+        assert_eq!(patched.to_level_order(), tree().to_level_order());
+
+        let edits = tree().diff(&empty);
+        let mut patched = tree();
+        patched.apply(&edits);
+        assert!(patched.is_empty());
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        let mut tree = tree();
+        let remove = TreeEdit::Remove {
+            path: vec![false, true],
+            subtree: vec![],
+        };
+        assert_eq!(
+            tree.try_apply(&[remove]),
+            Err(TreeError::PathNotFound { depth: 2 })
+        );
+
+        let insert = TreeEdit::Insert {
+            path: vec![true, true],
+            subtree: vec![Some(9), None, None],
+        };
+        assert_eq!(
+            tree.try_apply(&[insert]),
+            Err(TreeError::PathTaken { depth: 2 })
+        );
+
+        let change = TreeEdit::ChangeKey {
+            path: vec![true, false, true],
+            old: 0,
+            new: 9,
+        };
+        assert_eq!(
+            tree.try_apply(&[change]),
+            Err(TreeError::PathNotFound { depth: 2 })
+        );
+        assert_eq!(tree.to_level_order(), "[1,2,3,4,null,null,5]");
+    }
+
+    #[test]
+    #[should_panic(expected = "Node at the end of the path already exists")]
+    fn test_apply_panics() {
+        tree().apply(&[TreeEdit::Insert {
+            path: vec![],
+            subtree: vec![Some(1), None, None],
+        }]);
+    }
+
+    #[test]
+    fn test_random_diffs() {
+        let mut generator = TreeGenerator::new(16);
+        for n in 0..60 {
+            let old = generator.tree(n, Shape::RandomBinary, 0i32..=3);
+            let m = generator.below(60);
+            let new = generator.tree(m, Shape::RandomBinary, 0i32..=3);
+
+            let mut patched = Tree::new();
+            patched.apply(&Tree::new().diff(&old));
+            patched.apply(&old.diff(&new));
+            assert_eq!(patched.to_level_order(), new.to_level_order());
+            assert!(patched.diff(&new).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let n = 200_000;
+        let old = TreeGenerator::new(17).tree(n, Shape::Chain, 0u32..=0);
+        let mut new = TreeGenerator::new(17).tree(n, Shape::Chain, 0u32..=0);
+        new.nodes[n - 1].key = 1;
+
+        let edits = old.diff(&new);
+        assert_eq!(edits.len(), 1);
+        let mut patched = TreeGenerator::new(17).tree(n, Shape::Chain, 0u32..=0);
+        patched.apply(&edits);
+        assert!(patched.diff(&new).is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// Error returned by the fallible operations on a tree, e.g. `try_add_node`.
+/// The panicking operations panic with the same message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeError {
+    /// The parent of the new node is out of range or has been removed
+    ParentNotFound { parent_id: usize },
+    /// The parent has the child on that side already set
+    ChildAlreadySet { parent_id: usize, is_left: bool },
+    /// The node id is out of range or has been removed
+    NodeNotFound { node_id: usize },
+    /// The path from the root leaves the tree after `depth` steps
+    PathNotFound { depth: usize },
+    /// The path from the root ends on a node that should not exist
+    PathTaken { depth: usize },
+    /// The root can only be set on an empty tree
+    RootAlreadySet,
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeError::ParentNotFound { .. } => write!(f, "Parent node id does not exist"),
+            TreeError::ChildAlreadySet { is_left: true, .. } => {
+                write!(f, "Parent node has the left child already set")
+            }
+            TreeError::ChildAlreadySet { is_left: false, .. } => {
+                write!(f, "Parent node has the right child already set")
+            }
+            TreeError::NodeNotFound { .. } => write!(f, "Node id does not exist"),
+            TreeError::PathNotFound { .. } => {
+                write!(f, "Node at the end of the path does not exist")
+            }
+            TreeError::PathTaken { .. } => {
+                write!(f, "Node at the end of the path already exists")
+            }
+            TreeError::RootAlreadySet => write!(f, "The tree has a root already"),
+        }
+    }
+}
+
+impl std::error::Error for TreeError {}
@@ -0,0 +1,285 @@
+use std::ops::Add;
+
+use crate::{
+    segment::{RangeAggregate, SegmentTree},
+    Tree, TreeError,
+};
+
+/// Flattening of a tree in pre-order, where every subtree is a contiguous range.
+/// The subtree of `v` is made by the positions from `tin[v]` to `tout[v]`, both included.
+pub struct EulerTour<T> {
+    /// Entry time of every node id, its position in the order.
+    /// None for the ids that are not in the tree.
+    pub tin: Vec<Option<usize>>,
+    /// Exit time of every node id, the position of the last node of its subtree.
+    /// None for the ids that are not in the tree.
+    pub tout: Vec<Option<usize>>,
+    /// Node id at every position
+    pub order: Vec<usize>,
+    /// Key at every position
+    pub keys: Vec<T>,
+}
+
+impl<T: Copy> Tree<T> {
+    /// Returns the Euler tour of the tree in O(n)
+    pub fn euler_tour(&self) -> EulerTour<T> {
+        let n = self.nodes.len();
+        let order: Vec<usize> = self.iter_preorder().map(|(id, _)| id).collect();
+        let keys = order.iter().map(|&id| self.nodes[id].key).collect();
+
+        let mut tin = vec![None; n];
+        for (position, &id) in order.iter().enumerate() {
+            tin[id] = Some(position);
+        }
+
+        // The last node of a subtree is the last node of the subtree of its
+        // right child, or of its left child, or the node itself.
+        // The children are after their parent in pre-order, so visit it backward.
+        let mut tout = vec![None; n];
+        for &id in order.iter().rev() {
+            let node = &self.nodes[id];
+            tout[id] = node
+                .id_right
+                .or(node.id_left)
+                .map_or(tin[id], |child| tout[child]);
+        }
+
+        EulerTour {
+            tin,
+            tout,
+            order,
+            keys,
+        }
+    }
+}
+
+/// Subtree sum and maximum queries with point updates, over the Euler tour of a tree.
+/// Every query or update takes O(log n).
+/// The index is a snapshot: `add` changes the keys of the index, not of the tree.
+pub struct SubtreeIndex<T> {
+    tin: Vec<Option<usize>>,
+    tout: Vec<Option<usize>>,
+    segment_tree: SegmentTree<T>,
+}
+
+impl<T: Ord + Add<Output = T> + Copy> SubtreeIndex<T> {
+    /// Builds the index of `tree` in O(n)
+    pub fn new(tree: &Tree<T>) -> Self {
+        let EulerTour {
+            tin, tout, keys, ..
+        } = tree.euler_tour();
+        SubtreeIndex {
+            tin,
+            tout,
+            segment_tree: SegmentTree::new(&keys),
+        }
+    }
+
+    /// Returns the sum and the maximum of the keys in the subtree of `v`
+    ///
+    /// # Panics
+    /// Panics if `v` is not in the tree.
+    pub fn subtree_query(&self, v: usize) -> RangeAggregate<T> {
+        self.try_subtree_query(v)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the aggregate of the subtree like `subtree_query`, or an error
+    /// if `v` is not in the tree
+    pub fn try_subtree_query(&self, v: usize) -> Result<RangeAggregate<T>, TreeError> {
+        let (first, last) = self.try_range(v)?;
+        Ok(self
+            .segment_tree
+            .query(first, last)
+            .expect("A subtree contains at least one node"))
+    }
+
+    /// Returns the sum of the keys in the subtree of `v`
+    ///
+    /// # Panics
+    /// Panics if `v` is not in the tree.
+    pub fn subtree_sum(&self, v: usize) -> T {
+        self.subtree_query(v).sum
+    }
+
+    /// Returns the maximum key in the subtree of `v`
+    ///
+    /// # Panics
+    /// Panics if `v` is not in the tree.
+    pub fn subtree_max(&self, v: usize) -> T {
+        self.subtree_query(v).max
+    }
+
+    /// Adds `delta` to the key of `v`
+    ///
+    /// # Panics
+    /// Panics if `v` is not in the tree.
+    pub fn add(&mut self, v: usize, delta: T) {
+        self.try_add(v, delta)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Adds `delta` to the key of `v`, or returns an error if `v` is not in the tree
+    pub fn try_add(&mut self, v: usize, delta: T) -> Result<(), TreeError> {
+        let (position, _) = self.try_range(v)?;
+        let key = self.segment_tree.get(position);
+        self.segment_tree.set(position, key + delta);
+        Ok(())
+    }
+
+    /// Returns the range of positions of the subtree of `v`
+    fn try_range(&self, v: usize) -> Result<(usize, usize), TreeError> {
+        match (self.tin.get(v), self.tout.get(v)) {
+            (Some(&Some(first)), Some(&Some(last))) => Ok((first, last)),
+            _ => Err(TreeError::NodeNotFound { node_id: v }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Shape, SumMonoid, TreeGenerator};
+
+    fn tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 2, true); // id 1
+        tree.add_node(0, -3, false); // id 2
+        tree.add_node(1, 7, true); // id 3
+        tree.add_node(1, 4, false); // id 4
+        tree.add_node(4, 20, true); // id 5
+        tree.add_node(2, 3, false); // id 6
+
+        //        10
+        //      /    \
+        //     2     -3
+        //    / \      \
+        //   7   4      3
+        //      /
+        //    20
+        tree
+    }
+
+    #[test]
+    fn test_euler_tour() {
+        let tour = tree().euler_tour();
+
+        assert_eq!(tour.order, vec![0, 1, 3, 4, 5, 2, 6]);
+        assert_eq!(tour.keys, vec![10, 2, 7, 4, 20, -3, 3]);
+        assert_eq!(
+            tour.tin,
+            vec![
+                Some(0),
+                Some(1),
+                Some(5),
+                Some(2),
+                Some(3),
+                Some(4),
+                Some(6)
+            ]
+        );
+        assert_eq!(
+            tour.tout,
+            vec![
+                Some(6),
+                Some(4),
+                Some(6),
+                Some(2),
+                Some(4),
+                Some(4),
+                Some(6)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_euler_tour_removed_nodes() {
+        let mut tree = tree();
+        tree.remove_subtree(1);
+        let tour = tree.euler_tour();
+
+        assert_eq!(tour.order, vec![0, 2, 6]);
+        assert_eq!(tour.tin[4], None);
+        assert_eq!(tour.tout[0], Some(2));
+    }
+
+    #[test]
+    fn test_subtree_query() {
+        let index = SubtreeIndex::new(&tree());
+
+        assert_eq!(index.subtree_query(0), RangeAggregate { sum: 43, max: 20 });
+        assert_eq!(index.subtree_query(1), RangeAggregate { sum: 33, max: 20 });
+        assert_eq!(index.subtree_sum(2), 0);
+        assert_eq!(index.subtree_max(2), 3);
+        assert_eq!(index.subtree_sum(5), 20);
+    }
+
+    #[test]
+    fn test_add() {
+        let mut index = SubtreeIndex::new(&tree());
+        index.add(3, 50);
+        index.add(6, -10);
+
+        assert_eq!(index.subtree_query(1), RangeAggregate { sum: 83, max: 57 });
+        assert_eq!(index.subtree_query(2), RangeAggregate { sum: -10, max: -3 });
+        assert_eq!(index.subtree_sum(0), 83);
+    }
+
+    #[test]
+    fn test_missing_node() {
+        let mut tree = tree();
+        tree.remove_subtree(4);
+        let mut index = SubtreeIndex::new(&tree);
+
+        let err = TreeError::NodeNotFound { node_id: 5 };
+        assert_eq!(index.try_subtree_query(5), Err(err));
+        assert_eq!(index.try_add(5, 1), Err(err));
+        assert_eq!(
+            index.try_subtree_query(100),
+            Err(TreeError::NodeNotFound { node_id: 100 })
+        );
+        assert_eq!(index.subtree_sum(1), 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id does not exist")]
+    fn test_missing_node_query() {
+        SubtreeIndex::new(&tree()).subtree_sum(7);
+    }
+
+    #[test]
+    fn test_random_updates() {
+        let mut generator = TreeGenerator::new(5);
+        for n in 1..40 {
+            let mut tree = generator.tree(n, Shape::RandomBinary, -50i64..=49);
+            let mut index = SubtreeIndex::new(&tree);
+
+            for _ in 0..20 {
+                let (id, delta) = (generator.below(n), generator.key(&(-50..=49)));
+                index.add(id, delta);
+                tree.nodes[id].key += delta;
+
+                // Compare with the sums recomputed from scratch
+                let sums = tree.aggregate_subtrees::<SumMonoid<i64>>(|&key| key);
+                for (id, &sum) in sums.iter().enumerate() {
+                    assert_eq!(index.subtree_sum(id), sum);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let n = 100_000;
+        let mut tree = Tree::with_root(1i64);
+        for id in 0..n - 1 {
+            tree.add_node(id, 1, id % 2 == 0);
+        }
+        let mut index = SubtreeIndex::new(&tree);
+
+        assert_eq!(index.subtree_sum(0), n as i64);
+        assert_eq!(index.subtree_sum(n - 10), 10);
+        index.add(n - 1, 99);
+        assert_eq!(index.subtree_max(12_345), 100);
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use crate::Tree;
+
+/// Table of the canonical ids: two subtrees get the same id if and only if they
+/// have the same shape and the same keys, wherever they are and whatever their
+/// node ids are. A subtree is identified by `(key, left id, right id)`.
+type Canonical<'a, T> = HashMap<(&'a T, Option<usize>, Option<usize>), usize>;
+
+impl<T: Hash + Eq> Tree<T> {
+    /// Returns the Merkle hash of every subtree, the hash of `(key, left hash, right hash)`
+    /// with 0 for a missing child. Equal subtrees have equal hashes, different subtrees
+    /// have different hashes with high probability.
+    /// The result is indexed by node id, the ids that are not in the tree get 0.
+    pub fn subtree_hashes(&self) -> Vec<u64> {
+        let mut hashes = vec![0; self.nodes.len()];
+
+        // The children are visited before the node, so their hashes are ready
+        for (id, key) in self.iter_postorder() {
+            let node = &self.nodes[id];
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            node.id_left
+                .map_or(0, |left| hashes[left])
+                .hash(&mut hasher);
+            node.id_right
+                .map_or(0, |right| hashes[right])
+                .hash(&mut hasher);
+            hashes[id] = hasher.finish();
+        }
+        hashes
+    }
+
+    /// Returns true if the two trees have the same shape and the same keys,
+    /// the node ids don't matter. It runs in O(n + m).
+    pub fn is_isomorphic(&self, other: &Tree<T>) -> bool {
+        let mut canonical = Canonical::new();
+        let ids = self.canonical_ids(&mut canonical, false);
+        let other_ids = other.canonical_ids(&mut canonical, false);
+        Self::root_id(self, &ids) == Self::root_id(other, &other_ids)
+    }
+
+    /// Returns true if the tree is equal to `other` with the left and right
+    /// children swapped at every node. It runs in O(n + m).
+    pub fn is_mirror_of(&self, other: &Tree<T>) -> bool {
+        let mut canonical = Canonical::new();
+        let ids = self.canonical_ids(&mut canonical, false);
+        let other_ids = other.canonical_ids(&mut canonical, true);
+        Self::root_id(self, &ids) == Self::root_id(other, &other_ids)
+    }
+
+    /// Returns the id of a node whose subtree is equal to the whole `other` tree,
+    /// the first in pre-order, or None if there isn't one or `other` is empty.
+    /// It runs in O(n + m).
+    pub fn find_subtree(&self, other: &Tree<T>) -> Option<usize> {
+        let mut canonical = Canonical::new();
+        let other_ids = other.canonical_ids(&mut canonical, false);
+        let target = Self::root_id(other, &other_ids)?;
+
+        let ids = self.canonical_ids(&mut canonical, false);
+        self.iter_preorder()
+            .map(|(id, _)| id)
+            .find(|&id| ids[id] == Some(target))
+    }
+
+    /// Assigns the canonical id of every subtree, adding the new ones to `canonical`.
+    /// With `mirror` the children are swapped, so the ids are the ones of the mirrored tree.
+    /// The result is indexed by node id, None for the ids that are not in the tree.
+    fn canonical_ids<'a>(
+        &'a self,
+        canonical: &mut Canonical<'a, T>,
+        mirror: bool,
+    ) -> Vec<Option<usize>> {
+        let mut ids = vec![None; self.nodes.len()];
+
+        for (id, key) in self.iter_postorder() {
+            let node = &self.nodes[id];
+            let left = node.id_left.and_then(|left| ids[left]);
+            let right = node.id_right.and_then(|right| ids[right]);
+            let signature = if mirror {
+                (key, right, left)
+            } else {
+                (key, left, right)
+            };
+
+            let next = canonical.len();
+            ids[id] = Some(*canonical.entry(signature).or_insert(next));
+        }
+        ids
+    }
+
+    /// Returns the canonical id of the root of `tree`, None if it is empty
+    fn root_id(tree: &Tree<T>, ids: &[Option<usize>]) -> Option<usize> {
+        tree.root.and_then(|root| ids[root])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 2, true); // id 1
+        tree.add_node(0, 3, false); // id 2
+        tree.add_node(1, 4, true); // id 3
+        tree.add_node(1, 5, false); // id 4
+        tree.add_node(2, 2, true); // id 5
+        tree.add_node(5, 4, true); // id 6
+        tree.add_node(5, 5, false); // id 7
+
+        //        1
+        //      /   \
+        //     2     3
+        //    / \   /
+        //   4   5 2
+        //        / \
+        //       4   5
+        tree
+    }
+
+    /// The same tree of `tree`, with the nodes added in another order
+    fn renumbered() -> Tree<i32> {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 3, false); // id 1
+        tree.add_node(1, 2, true); // id 2
+        tree.add_node(2, 5, false); // id 3
+        tree.add_node(2, 4, true); // id 4
+        tree.add_node(0, 2, true); // id 5
+        tree.add_node(5, 5, false); // id 6
+        tree.add_node(5, 4, true); // id 7
+        tree
+    }
+
+    /// The mirror of `tree`
+    fn mirrored() -> Tree<i32> {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 3, true); // id 1
+        tree.add_node(0, 2, false); // id 2
+        tree.add_node(1, 2, false); // id 3
+        tree.add_node(3, 5, true); // id 4
+        tree.add_node(3, 4, false); // id 5
+        tree.add_node(2, 5, true); // id 6
+        tree.add_node(2, 4, false); // id 7
+        tree
+    }
+
+    #[test]
+    fn test_subtree_hashes() {
+        let tree = tree();
+        let hashes = tree.subtree_hashes();
+
+        // The two subtrees 2 -> (4, 5) are equal
+        assert_eq!(hashes[1], hashes[5]);
+        assert_eq!(hashes[3], hashes[6]);
+        assert_ne!(hashes[0], hashes[2]);
+        assert_ne!(hashes[3], hashes[4]);
+
+        let other = renumbered().subtree_hashes();
+        assert_eq!(hashes[0], other[0]);
+        assert_ne!(hashes[0], mirrored().subtree_hashes()[0]);
+    }
+
+    #[test]
+    fn test_is_isomorphic() {
+        let tree = tree();
+
+        assert!(tree.is_isomorphic(&renumbered()));
+        assert!(tree.is_isomorphic(&tree));
+        assert!(!tree.is_isomorphic(&mirrored()));
+
+        let mut other = renumbered();
+        other.add_node(6, 0, true);
+        assert!(!tree.is_isomorphic(&other));
+
+        // Same keys in pre-order, different shape
+        let mut chain = Tree::with_root(1);
+        chain.add_node(0, 2, true);
+        let mut other = Tree::with_root(1);
+        other.add_node(0, 2, false);
+        assert!(!chain.is_isomorphic(&other));
+    }
+
+    #[test]
+    fn test_is_mirror_of() {
+        let tree = tree();
+
+        assert!(tree.is_mirror_of(&mirrored()));
+        assert!(mirrored().is_mirror_of(&tree));
+        assert!(!tree.is_mirror_of(&renumbered()));
+
+        // A symmetric tree is the mirror of itself
+        let mut symmetric = Tree::with_root(1);
+        symmetric.add_node(0, 2, true);
+        symmetric.add_node(0, 2, false);
+        assert!(symmetric.is_mirror_of(&symmetric));
+    }
+
+    #[test]
+    fn test_find_subtree() {
+        let tree = tree();
+
+        let mut pattern = Tree::with_root(2);
+        pattern.add_node(0, 4, true);
+        pattern.add_node(0, 5, false);
+        assert_eq!(tree.find_subtree(&pattern), Some(1));
+
+        let pattern = Tree::with_root(5);
+        assert_eq!(tree.find_subtree(&pattern), Some(4));
+
+        // A node with the same key but a different subtree
+        let pattern = Tree::with_root(3);
+        assert_eq!(tree.find_subtree(&pattern), None);
+
+        assert_eq!(tree.find_subtree(&renumbered()), Some(0));
+        assert_eq!(tree.find_subtree(&Tree::new()), None);
+    }
+
+    #[test]
+    fn test_empty_trees() {
+        let empty: Tree<i32> = Tree::new();
+
+        assert!(empty.is_isomorphic(&Tree::new()));
+        assert!(empty.is_mirror_of(&Tree::new()));
+        assert!(!empty.is_isomorphic(&tree()));
+        assert_eq!(empty.find_subtree(&tree()), None);
+    }
+
+    #[test]
+    fn test_removed_nodes() {
+        let mut tree = tree();
+        tree.remove_subtree(5);
+
+        let mut expected = Tree::with_root(1);
+        expected.add_node(0, 2, true);
+        expected.add_node(0, 3, false);
+        expected.add_node(1, 4, true);
+        expected.add_node(1, 5, false);
+
+        assert!(tree.is_isomorphic(&expected));
+        assert_eq!(tree.subtree_hashes()[5], 0);
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let n = 100_000;
+        let mut tree = Tree::with_root(0u32);
+        let mut mirror = Tree::with_root(0u32);
+        for id in 0..n - 1 {
+            tree.add_node(id, id as u32 % 3, id % 2 == 0);
+            mirror.add_node(id, id as u32 % 3, id % 2 == 1);
+        }
+
+        assert!(tree.is_mirror_of(&mirror));
+        assert!(!tree.is_isomorphic(&mirror));
+
+        // The lower half of the chain, node j has key (j - 1) % 3
+        let m = n / 2;
+        let mut pattern = Tree::with_root((m as u32 - 1) % 3);
+        for j in m + 1..n {
+            pattern.add_node(j - 1 - m, (j as u32 - 1) % 3, (j - 1) % 2 == 0);
+        }
+        assert_eq!(tree.find_subtree(&pattern), Some(m));
+    }
+}
@@ -0,0 +1,295 @@
+use std::ops::Add;
+
+use crate::{
+    segment::{self, RangeAggregate, SegmentTree},
+    Tree, TreeError,
+};
+
+/// Heavy-light decomposition of a tree, with the keys stored in a segment tree
+/// over the decomposition order.
+/// The heavy child of a node is the child with the largest subtree, the heavy
+/// chains are contiguous in the order, so every path is split in O(log n) ranges
+/// and every query or update takes O(log^2 n).
+/// The index is a snapshot: `update_key` changes the keys of the index, not of the tree.
+pub struct HeavyLight<T> {
+    // Parent of every node id, the root is the parent of itself
+    parent: Vec<usize>,
+    // Depth of every node id, None for the ids that are not in the tree
+    depth: Vec<Option<usize>>,
+    // Top node of the heavy chain of every node id
+    head: Vec<usize>,
+    // Position of every node id in the segment tree
+    position: Vec<usize>,
+    segment_tree: SegmentTree<T>,
+}
+
+impl<T: Ord + Add<Output = T> + Copy> HeavyLight<T> {
+    /// Builds the decomposition of `tree` in O(n)
+    pub fn new(tree: &Tree<T>) -> Self {
+        let n = tree.nodes.len();
+        let mut parent: Vec<usize> = (0..n).collect();
+        let mut depth: Vec<Option<usize>> = vec![None; n];
+        let order: Vec<usize> = tree.iter_preorder().map(|(id, _)| id).collect();
+
+        if let Some(root) = tree.root {
+            depth[root] = Some(0);
+        }
+        for &id in &order {
+            for child in tree.children(id) {
+                parent[child] = id;
+                depth[child] = depth[id].map(|depth| depth + 1);
+            }
+        }
+
+        // The children are after their parent in pre-order, so their size is ready
+        let mut size = vec![1; n];
+        let mut heavy: Vec<Option<usize>> = vec![None; n];
+        for &id in order.iter().rev() {
+            for child in tree.children(id) {
+                size[id] += size[child];
+            }
+            heavy[id] = tree.children(id).max_by_key(|&child| size[child]);
+        }
+
+        // Walk every chain from its head, the light children start new chains
+        let mut head: Vec<usize> = (0..n).collect();
+        let mut position = vec![0; n];
+        let mut keys = Vec::with_capacity(order.len());
+        let mut stack: Vec<usize> = tree.root.into_iter().collect();
+        while let Some(chain_head) = stack.pop() {
+            let mut current = Some(chain_head);
+            while let Some(id) = current {
+                head[id] = chain_head;
+                position[id] = keys.len();
+                keys.push(tree.nodes[id].key);
+
+                stack.extend(tree.children(id).filter(|&child| Some(child) != heavy[id]));
+                current = heavy[id];
+            }
+        }
+
+        HeavyLight {
+            parent,
+            depth,
+            head,
+            position,
+            segment_tree: SegmentTree::new(&keys),
+        }
+    }
+
+    /// Returns the sum and the maximum of the keys on the path between `u` and `v`,
+    /// both included.
+    ///
+    /// # Panics
+    /// Panics if `u` or `v` is not in the tree.
+    pub fn path_query(&self, u: usize, v: usize) -> RangeAggregate<T> {
+        self.try_path_query(u, v)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the aggregate of the path like `path_query`, or an error if `u` or `v`
+    /// is not in the tree
+    pub fn try_path_query(&self, u: usize, v: usize) -> Result<RangeAggregate<T>, TreeError> {
+        self.try_depth(u)?;
+        self.try_depth(v)?;
+        let (mut u, mut v) = (u, v);
+        let mut aggregate = None;
+
+        // Move up the node whose chain head is deeper until both are on the same chain
+        while self.head[u] != self.head[v] {
+            let (head_u, head_v) = (self.head[u], self.head[v]);
+            if self.depth[head_u] < self.depth[head_v] {
+                std::mem::swap(&mut u, &mut v);
+            }
+            let head = self.head[u];
+            aggregate = segment::merge(
+                aggregate,
+                self.segment_tree
+                    .query(self.position[head], self.position[u]),
+            );
+            u = self.parent[head];
+        }
+
+        let (first, last) = if self.position[u] < self.position[v] {
+            (self.position[u], self.position[v])
+        } else {
+            (self.position[v], self.position[u])
+        };
+        let aggregate = segment::merge(aggregate, self.segment_tree.query(first, last));
+        Ok(aggregate.expect("A path contains at least one node"))
+    }
+
+    /// Returns the sum of the keys on the path between `u` and `v`
+    ///
+    /// # Panics
+    /// Panics if `u` or `v` is not in the tree.
+    pub fn path_sum(&self, u: usize, v: usize) -> T {
+        self.path_query(u, v).sum
+    }
+
+    /// Returns the maximum key on the path between `u` and `v`
+    ///
+    /// # Panics
+    /// Panics if `u` or `v` is not in the tree.
+    pub fn path_max(&self, u: usize, v: usize) -> T {
+        self.path_query(u, v).max
+    }
+
+    /// Returns the key of `u` in the index
+    ///
+    /// # Panics
+    /// Panics if `u` is not in the tree.
+    pub fn key(&self, u: usize) -> T {
+        self.try_depth(u).unwrap_or_else(|err| panic!("{}", err));
+        self.segment_tree.get(self.position[u])
+    }
+
+    /// Replaces the key of `u` with `value`
+    ///
+    /// # Panics
+    /// Panics if `u` is not in the tree.
+    pub fn update_key(&mut self, u: usize, value: T) {
+        self.try_update_key(u, value)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Replaces the key of `u` with `value`, or returns an error if `u` is not in the tree
+    pub fn try_update_key(&mut self, u: usize, value: T) -> Result<(), TreeError> {
+        self.try_depth(u)?;
+        self.segment_tree.set(self.position[u], value);
+        Ok(())
+    }
+
+    fn try_depth(&self, u: usize) -> Result<usize, TreeError> {
+        self.depth
+            .get(u)
+            .copied()
+            .flatten()
+            .ok_or(TreeError::NodeNotFound { node_id: u })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LcaIndex, Shape, TreeGenerator};
+
+    fn tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 2, true); // id 1
+        tree.add_node(0, -3, false); // id 2
+        tree.add_node(1, 7, true); // id 3
+        tree.add_node(1, 4, false); // id 4
+        tree.add_node(4, 20, true); // id 5
+        tree.add_node(4, -25, false); // id 6
+        tree.add_node(2, 3, false); // id 7
+
+        //        10
+        //      /    \
+        //     2     -3
+        //    / \      \
+        //   7   4      3
+        //      / \
+        //    20  -25
+        tree
+    }
+
+    /// Computes the aggregate of the path walking up to the lca
+    fn brute_force(tree: &Tree<i32>, keys: &[i32], u: usize, v: usize) -> RangeAggregate<i32> {
+        let index = LcaIndex::new(tree);
+        let lca = index.lca(u, v);
+        let mut path = vec![lca];
+        for mut node in [u, v] {
+            while node != lca {
+                path.push(node);
+                node = index.kth_ancestor(node, 1).unwrap();
+            }
+        }
+        RangeAggregate {
+            sum: path.iter().map(|&id| keys[id]).sum(),
+            max: path.iter().map(|&id| keys[id]).max().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_path_query() {
+        let hld = HeavyLight::new(&tree());
+
+        assert_eq!(hld.path_query(5, 6), RangeAggregate { sum: -1, max: 20 });
+        assert_eq!(hld.path_query(5, 7), RangeAggregate { sum: 36, max: 20 });
+        assert_eq!(hld.path_query(3, 3), RangeAggregate { sum: 7, max: 7 });
+        assert_eq!(hld.path_sum(0, 6), -9);
+        assert_eq!(hld.path_max(6, 2), 10);
+    }
+
+    #[test]
+    fn test_update_key() {
+        let tree = tree();
+        let mut hld = HeavyLight::new(&tree);
+
+        hld.update_key(0, 100);
+        hld.update_key(5, -1);
+        assert_eq!(hld.key(0), 100);
+        assert_eq!(hld.path_query(5, 7), RangeAggregate { sum: 105, max: 100 });
+        assert_eq!(hld.path_query(5, 6), RangeAggregate { sum: -22, max: 4 });
+
+        // The tree is not changed
+        assert_eq!(tree.nodes[0].key, 10);
+    }
+
+    #[test]
+    fn test_removed_nodes() {
+        let mut tree = tree();
+        tree.remove_subtree(4);
+        let mut hld = HeavyLight::new(&tree);
+
+        assert_eq!(hld.path_query(3, 7), RangeAggregate { sum: 19, max: 10 });
+
+        let err = TreeError::NodeNotFound { node_id: 5 };
+        assert_eq!(hld.try_path_query(5, 3), Err(err));
+        assert_eq!(hld.try_update_key(5, 1), Err(err));
+        assert_eq!(
+            hld.try_path_query(3, 100),
+            Err(TreeError::NodeNotFound { node_id: 100 })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id does not exist")]
+    fn test_missing_node_query() {
+        HeavyLight::new(&tree()).path_query(0, 8);
+    }
+
+    #[test]
+    fn test_random_trees() {
+        let mut generator = TreeGenerator::new(11);
+        for n in 1..40 {
+            let tree = generator.tree(n, Shape::RandomBinary, -50i32..=49);
+            let mut keys: Vec<i32> = tree.nodes.iter().map(|node| node.key).collect();
+            let mut hld = HeavyLight::new(&tree);
+
+            for _ in 0..50 {
+                let (u, v) = (generator.below(n), generator.below(n));
+                assert_eq!(hld.path_query(u, v), brute_force(&tree, &keys, u, v));
+
+                let (id, key) = (generator.below(n), generator.key(&(-50..=49)));
+                hld.update_key(id, key);
+                keys[id] = key;
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let n = 100_000;
+        let mut tree = Tree::with_root(1i64);
+        for id in 0..n - 1 {
+            tree.add_node(id, id as i64 + 2, id % 3 == 0);
+        }
+        let mut hld = HeavyLight::new(&tree);
+
+        assert_eq!(hld.path_sum(0, n - 1), (n * (n + 1) / 2) as i64);
+        hld.update_key(n / 2, 1_000_000);
+        assert_eq!(hld.path_max(n - 1, 12), 1_000_000);
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::VecDeque;
+
+use crate::{Tree, TreeError};
+
+/// Lowest common ancestor index of a tree, built with binary lifting.
+/// `up[k][v]` is the 2^k-th ancestor of `v` (the root is the ancestor of itself).
+/// Building the index takes O(n log n), every query takes O(log n).
+/// The index is a snapshot: it must be rebuilt after the tree changes.
+pub struct LcaIndex {
+    up: Vec<Vec<usize>>,
+    // Depth of every node id, None for the ids that are not in the tree
+    depth: Vec<Option<usize>>,
+}
+
+impl LcaIndex {
+    /// Builds the index of `tree` with a level-order visit
+    pub fn new<T>(tree: &Tree<T>) -> Self {
+        let n = tree.nodes.len();
+        let mut parent: Vec<usize> = (0..n).collect();
+        let mut depth: Vec<Option<usize>> = vec![None; n];
+
+        let mut queue: VecDeque<usize> = tree.root.into_iter().collect();
+        if let Some(root) = tree.root {
+            depth[root] = Some(0);
+        }
+        while let Some(id) = queue.pop_front() {
+            let node = &tree.nodes[id];
+            for child in node.id_left.into_iter().chain(node.id_right) {
+                parent[child] = id;
+                depth[child] = depth[id].map(|depth| depth + 1);
+                queue.push_back(child);
+            }
+        }
+
+        // Number of levels needed to jump over the deepest node
+        let max_depth = depth.iter().flatten().copied().max().unwrap_or(0);
+        let levels = (usize::BITS - max_depth.leading_zeros()).max(1) as usize;
+
+        let mut up = vec![parent];
+        for k in 1..levels {
+            let previous = &up[k - 1];
+            let level = previous
+                .iter()
+                .map(|&ancestor| previous[ancestor])
+                .collect();
+            up.push(level);
+        }
+
+        Self { up, depth }
+    }
+
+    /// Returns the depth of `u`, the root has depth 0.
+    ///
+    /// # Panics
+    /// Panics if `u` is not in the tree.
+    pub fn depth(&self, u: usize) -> usize {
+        self.try_depth(u).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the depth of `u`, or an error if `u` is not in the tree
+    pub fn try_depth(&self, u: usize) -> Result<usize, TreeError> {
+        self.depth
+            .get(u)
+            .copied()
+            .flatten()
+            .ok_or(TreeError::NodeNotFound { node_id: u })
+    }
+
+    /// Returns the ancestor of `u` that is `k` levels above it, `u` itself if `k` is 0.
+    /// It returns None if `k` is greater than the depth of `u`.
+    ///
+    /// # Panics
+    /// Panics if `u` is not in the tree.
+    pub fn kth_ancestor(&self, u: usize, k: usize) -> Option<usize> {
+        self.try_kth_ancestor(u, k)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the ancestor of `u` like `kth_ancestor`, or an error if `u` is not in the tree
+    pub fn try_kth_ancestor(&self, u: usize, k: usize) -> Result<Option<usize>, TreeError> {
+        if k > self.try_depth(u)? {
+            return Ok(None);
+        }
+        Ok(Some(self.jump(u, k)))
+    }
+
+    /// Returns the lowest common ancestor of `u` and `v`
+    ///
+    /// # Panics
+    /// Panics if `u` or `v` is not in the tree.
+    pub fn lca(&self, u: usize, v: usize) -> usize {
+        self.try_lca(u, v).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the lowest common ancestor of `u` and `v`, or an error if one of them
+    /// is not in the tree
+    pub fn try_lca(&self, u: usize, v: usize) -> Result<usize, TreeError> {
+        let (depth_u, depth_v) = (self.try_depth(u)?, self.try_depth(v)?);
+
+        // Bring both nodes at the same depth
+        let (mut u, mut v) = if depth_u > depth_v {
+            (self.jump(u, depth_u - depth_v), v)
+        } else {
+            (u, self.jump(v, depth_v - depth_u))
+        };
+        if u == v {
+            return Ok(u);
+        }
+
+        // Go up as long as the ancestors are different, from the longest jump.
+        // At the end u and v are the children of the lca.
+        for level in self.up.iter().rev() {
+            if level[u] != level[v] {
+                u = level[u];
+                v = level[v];
+            }
+        }
+        Ok(self.up[0][u])
+    }
+
+    /// Returns the number of edges on the path between `u` and `v`
+    ///
+    /// # Panics
+    /// Panics if `u` or `v` is not in the tree.
+    pub fn distance(&self, u: usize, v: usize) -> usize {
+        self.try_distance(u, v)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the number of edges on the path between `u` and `v`, or an error
+    /// if one of them is not in the tree
+    pub fn try_distance(&self, u: usize, v: usize) -> Result<usize, TreeError> {
+        let lca = self.try_lca(u, v)?;
+        Ok(self.depth(u) + self.depth(v) - 2 * self.depth(lca))
+    }
+
+    /// Returns the ancestor `k` levels above `u`, given that `k` is at most the depth of `u`.
+    /// `k` is decomposed in powers of two, one jump for every bit set.
+    fn jump(&self, mut u: usize, k: usize) -> usize {
+        for (bit, level) in self.up.iter().enumerate() {
+            if k >> bit & 1 == 1 {
+                u = level[u];
+            }
+        }
+        u
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Tree<u32> {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 1, true); // id 1
+        tree.add_node(0, 1, false); // id 2
+        tree.add_node(1, 1, true); // id 3
+        tree.add_node(1, 1, false); // id 4
+        tree.add_node(4, 20, true); // id 5
+        tree.add_node(4, 25, false); // id 6
+        tree.add_node(2, 3, false); // id 7
+
+        //        0
+        //      /  \
+        //     1    2
+        //    / \    \
+        //   3   4    7
+        //      / \
+        //     5   6
+        tree
+    }
+
+    #[test]
+    fn test_depth() {
+        let index = LcaIndex::new(&tree());
+        let depths: Vec<usize> = (0..8).map(|u| index.depth(u)).collect();
+        assert_eq!(depths, vec![0, 1, 1, 2, 2, 3, 3, 2]);
+    }
+
+    #[test]
+    fn test_lca() {
+        let index = LcaIndex::new(&tree());
+
+        assert_eq!(index.lca(5, 6), 4);
+        assert_eq!(index.lca(5, 3), 1);
+        assert_eq!(index.lca(6, 7), 0);
+        assert_eq!(index.lca(4, 5), 4);
+        assert_eq!(index.lca(5, 4), 4);
+        assert_eq!(index.lca(3, 3), 3);
+        assert_eq!(index.lca(0, 6), 0);
+    }
+
+    #[test]
+    fn test_distance() {
+        let index = LcaIndex::new(&tree());
+
+        assert_eq!(index.distance(5, 6), 2);
+        assert_eq!(index.distance(5, 7), 5);
+        assert_eq!(index.distance(0, 5), 3);
+        assert_eq!(index.distance(2, 2), 0);
+    }
+
+    #[test]
+    fn test_kth_ancestor() {
+        let index = LcaIndex::new(&tree());
+
+        assert_eq!(index.kth_ancestor(5, 0), Some(5));
+        assert_eq!(index.kth_ancestor(5, 1), Some(4));
+        assert_eq!(index.kth_ancestor(5, 2), Some(1));
+        assert_eq!(index.kth_ancestor(5, 3), Some(0));
+        assert_eq!(index.kth_ancestor(5, 4), None);
+    }
+
+    #[test]
+    fn test_removed_nodes() {
+        let mut tree = tree();
+        tree.remove_subtree(4);
+        let index = LcaIndex::new(&tree);
+
+        assert_eq!(index.lca(3, 7), 0);
+        assert_eq!(index.distance(3, 7), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id does not exist")]
+    fn test_removed_node_query() {
+        let mut tree = tree();
+        tree.remove_subtree(4);
+        LcaIndex::new(&tree).depth(5);
+    }
+
+    #[test]
+    fn test_long_chain() {
+        // A degenerate tree, every node is a child of the previous one
+        let n = 100_000;
+        let mut tree = Tree::with_root(0u32);
+        for id in 0..n - 1 {
+            tree.add_node(id, 0, id % 7 == 0);
+        }
+        let index = LcaIndex::new(&tree);
+
+        assert_eq!(index.depth(n - 1), n - 1);
+        assert_eq!(index.lca(n - 1, 12_345), 12_345);
+        assert_eq!(index.distance(n - 1, 12_345), n - 1 - 12_345);
+        assert_eq!(index.kth_ancestor(n - 1, 77_777), Some(n - 1 - 77_777));
+    }
+
+    #[test]
+    fn test_try_queries() {
+        let mut tree = tree();
+        tree.remove_subtree(4);
+        let index = LcaIndex::new(&tree);
+
+        let err = TreeError::NodeNotFound { node_id: 5 };
+        assert_eq!(index.try_depth(5), Err(err));
+        assert_eq!(index.try_lca(3, 5), Err(err));
+        assert_eq!(index.try_distance(5, 3), Err(err));
+        assert_eq!(index.try_kth_ancestor(5, 1), Err(err));
+        assert_eq!(
+            index.try_depth(100),
+            Err(TreeError::NodeNotFound { node_id: 100 })
+        );
+
+        assert_eq!(index.try_lca(3, 7), Ok(0));
+        assert_eq!(index.try_kth_ancestor(3, 3), Ok(None));
+    }
+}
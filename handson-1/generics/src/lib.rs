@@ -1,7 +1,58 @@
+mod avl;
+mod bst;
+mod centroid;
+mod diff;
+mod error;
+mod euler;
+mod hash;
+mod hld;
+mod lca;
+mod monoid;
+mod nary;
+mod navigation;
+mod overflow;
+mod parse;
+mod persistent;
+mod random;
+mod reroot;
+mod render;
+mod repair;
+mod segment;
+mod sequences;
+mod traversal;
+
+use std::cell::Cell;
+
+pub use avl::AvlTree;
+pub use bst::DuplicatePolicy;
+pub use centroid::CentroidTree;
+pub use diff::TreeEdit;
+pub use error::TreeError;
+pub use euler::{EulerTour, SubtreeIndex};
+pub use hld::HeavyLight;
+pub use lca::LcaIndex;
+pub use monoid::{CountMonoid, MaxMonoid, MinMonoid, Monoid, SumMonoid};
+pub use nary::{NaryLevelOrder, NaryPostorder, NaryPreorder, NaryTree};
+pub use navigation::NodeId;
+pub use overflow::{OverflowAdd, OverflowError};
+pub use parse::ParseTreeError;
+pub use persistent::{PersistentTree, Version};
+pub use random::{Shape, TreeGenerator};
+pub use reroot::{Eccentricity, Rerooting, SumOfDistances};
+pub use segment::RangeAggregate;
+pub use sequences::SequenceError;
+pub use traversal::{Inorder, LevelOrder, Postorder, Preorder};
+
 pub struct Node <T>{
     key: T,
     id_left: Option<usize>,
     id_right: Option<usize>,
+    // None for the root
+    id_parent: Option<usize>,
+    // Number of nodes in the subtree, valid unless the tree has `sizes_stale` set
+    size: Cell<usize>,
+    // false when the slot has been reclaimed and sits in the free list
+    alive: bool,
 }
 
 impl<T> Node<T> {
@@ -10,153 +61,461 @@ impl<T> Node<T> {
             key,
             id_left: None,
             id_right: None,
+            id_parent: None,
+            size: Cell::new(1),
+            alive: true,
         }
     }
 }
 
 pub struct Tree <T>{
     nodes: Vec<Node<T>>,
+    // None when the whole tree has been removed
+    root: Option<usize>,
+    // Ids of the reclaimed slots, reused by the next insertions
+    free: Vec<usize>,
+    // Where `insert` puts a key that is already in the tree
+    duplicates: DuplicatePolicy,
+    // true after `add_node`, until the next order-statistics query recounts the sizes
+    sizes_stale: Cell<bool>,
+}
+
+impl<T: Ord + std::ops::Add<Output=T> + Default + Copy> Default for Tree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 ///T needs to be bounded by some traits
 /// - Ord: permits to have the comparison operations
-/// - Add<Output=T>: permits to have the addition operation and 
-///                 guarantees that the output of an operation between T operands is always of type T
+/// - Add<Output=T>: permits to have the addition operation and
+///   guarantees that the output of an operation between T operands is always of type T
 /// - Default: to have a default value for whatever T is
 /// - Copy: instead of moving ownership, this creates a copy of the value (copy semantics)
+///
 /// Note: Ord trait doesn't cover floating number because NaN can occur and it is not handled  
 impl<T: Ord + std::ops::Add<Output=T> + Default + Copy> Tree<T> {
+    /// Returns an empty tree, the keys can be added with `insert`.
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            root: None,
+            free: Vec::new(),
+            duplicates: DuplicatePolicy::Reject,
+            sizes_stale: Cell::new(false),
+        }
+    }
+
     pub fn with_root(key: T) -> Self {
         Self {
             nodes: vec![Node::new(key)],
+            root: Some(0),
+            free: Vec::new(),
+            duplicates: DuplicatePolicy::Reject,
+            sizes_stale: Cell::new(false),
         }
     }
 
     /// Adds a child to the node with `parent_id` and returns the id of the new node.
     /// The new node has the specified `key`. The new node is the left  child of the  
     /// node `parent_id` iff `is_left` is `true`, the right child otherwise.
+    /// It takes O(1): the subtree sizes are marked stale and recounted in O(n)
+    /// by the next order-statistics query.
     ///
     /// # Panics
     /// Panics if the `parent_id` does not exist, or if the node `parent_id ` has  
-    /// the child already set.
+    /// the child already set. `try_add_node` returns the error instead.
     pub fn add_node(&mut self, parent_id: usize, key: T, is_left: bool) -> usize {
-        assert!(
-            parent_id < self.nodes.len(),
-            "Parent node id does not exist"
-        );
-        if is_left {
-            assert!(
-                self.nodes[parent_id].id_left.is_none(),
-                "Parent node has the left child already set"
-            );
+        self.try_add_node(parent_id, key, is_left)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Adds a child to the node with `parent_id` like `add_node`.
+    /// It returns an error if the `parent_id` does not exist, or if the node
+    /// `parent_id` has the child already set. In that case the tree is not modified.
+    pub fn try_add_node(
+        &mut self,
+        parent_id: usize,
+        key: T,
+        is_left: bool,
+    ) -> Result<usize, TreeError> {
+        self.check_free_slot(parent_id, is_left)?;
+        let child_id = self.attach(parent_id, key, is_left);
+        // Updating the sizes of all the ancestors would make every insertion O(depth)
+        self.sizes_stale.set(true);
+
+        Ok(child_id)
+    }
+
+    /// Removes the subtree rooted at `node_id` and returns the number of removed nodes.
+    /// The slots of the removed nodes are reclaimed and reused by the next insertions,
+    /// the ids of the remaining nodes do not change.
+    /// Removing the root leaves the tree empty.
+    ///
+    /// # Panics
+    /// Panics if the `node_id` does not exist. `try_remove_subtree` returns the error instead.
+    pub fn remove_subtree(&mut self, node_id: usize) -> usize {
+        self.try_remove_subtree(node_id)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Removes the subtree rooted at `node_id` like `remove_subtree`.
+    /// It returns an error if the `node_id` does not exist.
+    pub fn try_remove_subtree(&mut self, node_id: usize) -> Result<usize, TreeError> {
+        if !self.is_live(node_id) {
+            return Err(TreeError::NodeNotFound { node_id });
+        }
+        let parent_id = self.nodes[node_id].id_parent;
+        self.unlink(node_id);
+
+        let mut removed = 0;
+        let mut stack = vec![node_id];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            stack.extend(node.id_left);
+            stack.extend(node.id_right);
+            self.release(id);
+            removed += 1;
+        }
+        self.update_ancestor_sizes(parent_id, |size| size - removed);
+        Ok(removed)
+    }
+
+    /// Detaches the subtree rooted at `node_id` and returns it as a new tree.
+    /// In the new tree the nodes are renumbered in pre-order, so the detached
+    /// root has id 0. The slots are reclaimed as in `remove_subtree`.
+    ///
+    /// # Panics
+    /// Panics if the `node_id` does not exist. `try_detach` returns the error instead.
+    pub fn detach(&mut self, node_id: usize) -> Tree<T> {
+        self.try_detach(node_id)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Detaches the subtree rooted at `node_id` like `detach`.
+    /// It returns an error if the `node_id` does not exist.
+    pub fn try_detach(&mut self, node_id: usize) -> Result<Tree<T>, TreeError> {
+        if !self.is_live(node_id) {
+            return Err(TreeError::NodeNotFound { node_id });
+        }
+
+        let mut subtree = Tree::with_root(self.nodes[node_id].key);
+        // Pairs of (id in self, id in the subtree)
+        let mut stack = vec![(node_id, 0)];
+        while let Some((old_id, new_id)) = stack.pop() {
+            let (left, right) = (self.nodes[old_id].id_left, self.nodes[old_id].id_right);
+            // Push the right child first to visit the left one first
+            if let Some(right) = right {
+                let id = subtree.attach(new_id, self.nodes[right].key, false);
+                stack.push((right, id));
+            }
+            if let Some(left) = left {
+                let id = subtree.attach(new_id, self.nodes[left].key, true);
+                stack.push((left, id));
+            }
+        }
+
+        subtree.count_attached(0);
+        self.remove_subtree(node_id);
+        Ok(subtree)
+    }
+
+    /// Returns if `node_id` is the id of a node currently in the tree
+    fn is_live(&self, node_id: usize) -> bool {
+        node_id < self.nodes.len() && self.nodes[node_id].alive
+    }
+
+    /// Stores a new node with the given `key`, reusing a reclaimed slot if there is one.
+    fn alloc(&mut self, key: T) -> usize {
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = Node::new(key);
+                id
+            }
+            None => {
+                self.nodes.push(Node::new(key));
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Returns an error if `parent_id` does not exist or has the child on that side already set
+    fn check_free_slot(&self, parent_id: usize, is_left: bool) -> Result<(), TreeError> {
+        if !self.is_live(parent_id) {
+            return Err(TreeError::ParentNotFound { parent_id });
+        }
+        let child = if is_left {
+            self.nodes[parent_id].id_left
         } else {
-            assert!(
-                self.nodes[parent_id].id_right.is_none(),
-                "Parent node has the right child already set"
-            );
+            self.nodes[parent_id].id_right
+        };
+        match child {
+            Some(_) => Err(TreeError::ChildAlreadySet { parent_id, is_left }),
+            None => Ok(()),
         }
+    }
 
-        let child_id = self.nodes.len();
-        self.nodes.push(Node::new(key));
+    /// Stores a new node with `key` as the left or right child of `parent_id`,
+    /// that must not have that child already set.
+    fn attach(&mut self, parent_id: usize, key: T, is_left: bool) -> usize {
+        let child_id = self.alloc(key);
+        self.nodes[child_id].id_parent = Some(parent_id);
 
         let child = if is_left {
             &mut self.nodes[parent_id].id_left
         } else {
             &mut self.nodes[parent_id].id_right
         };
-
         *child = Some(child_id);
-
         child_id
     }
 
-    /// Returns the sum of all the keys in the tree
-    pub fn sum(&self) -> T {
-        self.rec_sum(Some(0))
+    /// Puts the slot of `node_id` in the free list. The node must be already unlinked.
+    fn release(&mut self, node_id: usize) {
+        let node = &mut self.nodes[node_id];
+        node.id_left = None;
+        node.id_right = None;
+        node.id_parent = None;
+        node.alive = false;
+        self.free.push(node_id);
     }
 
-    /// A private recursive function that computes the sum of
-    /// nodes in the subtree rooted at `node_id`.
-    fn rec_sum(&self, node_id: Option<usize>) -> T {
-        if let Some(id) = node_id {
-            assert!(id < self.nodes.len(), "Node id is out of range");
-            let node = &self.nodes[id];
+    /// Clears the link that points to `node_id`, either from its parent or from the root.
+    fn unlink(&mut self, node_id: usize) {
+        match self.nodes[node_id].id_parent.take() {
+            None => self.root = None,
+            Some(parent_id) => {
+                let parent = &mut self.nodes[parent_id];
+                if parent.id_left == Some(node_id) {
+                    parent.id_left = None;
+                } else {
+                    parent.id_right = None;
+                }
+            }
+        }
+    }
 
-            let sum_left = self.rec_sum(node.id_left);
-            let sum_right = self.rec_sum(node.id_right);
+    /// Applies `update` to the subtree size of `node_id` and of all its ancestors,
+    /// in O(depth). It does nothing if the sizes are stale, they are recounted anyway.
+    fn update_ancestor_sizes(&mut self, node_id: Option<usize>, update: impl Fn(usize) -> usize) {
+        if self.sizes_stale.get() {
+            return;
+        }
+        let mut current = node_id;
+        while let Some(id) = current {
+            let node = &self.nodes[id];
+            node.size.set(update(node.size.get()));
+            current = node.id_parent;
+        }
+    }
 
-            // Copy trait is essential here. Without the copy trait,
-            // the ownership of node.key is passed to the addition operation,
-            // in that way the Node instance has no longer the ownership and 
-            // node.key is no longer accessible.
-            return sum_left + sum_right + node.key;
+    /// Sets the subtree sizes of the nodes under `node_id` and adds its size to its
+    /// ancestors. It is for the subtrees built with `attach`, that leaves the sizes alone:
+    /// it takes O(size + depth) for the whole subtree, not O(depth) for every node.
+    fn count_attached(&mut self, node_id: usize) {
+        // Pre-order that visits the right child first, reversed it is a post-order
+        let mut order = vec![];
+        let mut stack = vec![node_id];
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            stack.extend(self.nodes[id].id_left);
+            stack.extend(self.nodes[id].id_right);
+        }
+        for &id in order.iter().rev() {
+            let node = &self.nodes[id];
+            node.size.set(1 + self.size_of(node.id_left) + self.size_of(node.id_right));
         }
-        T::default()
+
+        let size = self.nodes[node_id].size.get();
+        self.update_ancestor_sizes(self.nodes[node_id].id_parent, |ancestor| ancestor + size);
     }
 
-    /// Returns if the tree is a binary search tree or not
-    pub fn is_bst(&self) -> bool {
-        self.rec_bst(Some(0), &mut None)
+    /// Recounts all the subtree sizes in O(n) if `add_node` left them stale.
+    /// It takes `&self`, so the queries that need the sizes can call it.
+    fn refresh_sizes(&self) {
+        if !self.sizes_stale.get() {
+            return;
+        }
+        for (id, _) in self.iter_postorder() {
+            let node = &self.nodes[id];
+            node.size.set(1 + self.size_of(node.id_left) + self.size_of(node.id_right));
+        }
+        self.sizes_stale.set(false);
     }
 
-    /// Auxiliary function to check if the tree is a binary search tree with the in-order visit.
-    /// The parameters are the current node and a reference to value of the previous node
-    /// An alternative is to use a reference to the index of the previous node.
-    fn rec_bst(&self, current_node: Option<usize>, previous_node: &mut Option<T>) -> bool {
-        if let Some(current_id) = current_node {
-            assert!(current_id < self.nodes.len(), "Node id is out of range");
+    /// Returns the number of nodes in the subtree of `node_id`, 0 for None
+    fn size_of(&self, node_id: Option<usize>) -> usize {
+        node_id.map_or(0, |id| self.nodes[id].size.get())
+    }
 
-            let tree = &self.nodes;
+    /// Returns the sum of all the keys in the tree
+    pub fn sum(&self) -> T {
+        // Copy trait is essential here. Without the copy trait,
+        // the ownership of the key is passed to the addition operation,
+        // in that way the Node instance has no longer the ownership and
+        // the key is no longer accessible.
+        self.iter_preorder()
+            .fold(T::default(), |sum, (_, &key)| sum + key)
+    }
 
-            if !self.rec_bst(tree[current_id].id_left, previous_node) {
-                return false;
-            }
+    /// Returns if the tree is a binary search tree or not.
+    /// The keys are checked with the in-order visit, comparing each key
+    /// with the key of the previous node.
+    pub fn is_bst(&self) -> bool {
+        let mut previous_key: Option<T> = None;
 
-            if let Some(previous_id) = previous_node {
-                if *previous_id > tree[current_id].key {
+        for (_, &key) in self.iter_inorder() {
+            if let Some(previous_key) = previous_key {
+                if previous_key > key {
                     return false;
                 }
             }
+            previous_key = Some(key);
+        }
+        true
+    }
+
+    /// Returns the `k`-th smallest key, counting from 0 like `Iterator::nth`,
+    /// or None if the tree has `k` keys or fewer.
+    /// It takes O(height) with the subtree sizes, plus O(n) to recount them after
+    /// `add_node`. On a tree that is not a BST it returns the `k`-th key of the in-order visit.
+    pub fn kth_smallest(&self, k: usize) -> Option<T> {
+        self.refresh_sizes();
+        let mut k = k;
+        let mut current = self.root;
+        while let Some(id) = current {
+            let node = &self.nodes[id];
+            let left_size = self.size_of(node.id_left);
+            if k < left_size {
+                current = node.id_left;
+            } else if k == left_size {
+                return Some(node.key);
+            } else {
+                k -= left_size + 1;
+                current = node.id_right;
+            }
+        }
+        None
+    }
 
-            *previous_node = Some(tree[current_id].key);
+    /// Returns the number of keys strictly smaller than `key`, that is the position
+    /// `key` would have in the sorted keys. `key` doesn't need to be in the tree.
+    /// On a BST it takes O(height), plus O(n) to recount the sizes after `add_node`.
+    pub fn rank(&self, key: &T) -> usize {
+        self.count_below(key, false)
+    }
 
-            return self.rec_bst(tree[current_id].id_right, previous_node);
+    /// Returns the number of keys in `[low, high]`, both included.
+    /// On a BST it takes O(height), plus O(n) to recount the sizes after `add_node`.
+    pub fn count_in_range(&self, low: &T, high: &T) -> usize {
+        if low > high {
+            return 0;
         }
-        true
+        self.count_below(high, true) - self.count_below(low, false)
+    }
+
+    /// Returns the number of keys smaller than `key`, or equal to it if `or_equal`.
+    /// In a BST the keys of the left subtree are not greater than the node and the
+    /// ones of the right subtree are not smaller, so one path from the root is enough.
+    fn count_below(&self, key: &T, or_equal: bool) -> usize {
+        let below = |node_key: &T| node_key < key || (or_equal && node_key == key);
+        self.refresh_sizes();
+
+        let mut count = 0;
+        let mut current = self.root;
+        while let Some(id) = current {
+            let node = &self.nodes[id];
+            if below(&node.key) {
+                // The node and its whole left subtree are below the key
+                count += self.size_of(node.id_left) + 1;
+                current = node.id_right;
+            } else {
+                current = node.id_left;
+            }
+        }
+        count
     }
 
     /// Returns the maximum path sum. If the tree is empty, it returns None.
+    /// A path is a sequence of nodes connected by edges, with at least one node.
     pub fn max_path_sum(&self) -> Option<T> {
-        self.rec_max_path_sum(Some(0)).0
+        self.max_path().map(|(sum, _)| sum)
     }
 
-    fn rec_max_path_sum(&self, current_node: Option<usize>) -> (Option<T>, Option<T>) {
-        if let Some(current_id) = current_node {
-            assert!(current_id < self.nodes.len(), "Node id is out of range");
+    /// Returns the maximum path sum together with the ids of the nodes on the path,
+    /// from one end to the other. If the tree is empty, it returns None.
+    /// The path goes down into a child only if that increases the sum, so the
+    /// result is correct with negative keys too, e.g. it can be a single node.
+    /// The nodes are visited in post-order, so both children of a node
+    /// are computed before the node itself.
+    pub fn max_path(&self) -> Option<(T, Vec<usize>)> {
+        // The addition never fails, so the outer Option is always Some
+        self.fold_max_path(|key| key, |a, b| Some(a + b)).flatten()
+    }
 
+    /// Computes the maximum path like `max_path`, accumulating the keys converted
+    /// by `widen` with `add`. It returns None as soon as `add` returns None.
+    fn fold_max_path<S: Ord + Copy>(
+        &self,
+        widen: impl Fn(T) -> S,
+        add: impl Fn(S, S) -> Option<S>,
+    ) -> Option<Option<(S, Vec<usize>)>> {
+        // For every node: (maximum sum of a path that goes down from the node,
+        // child where that path continues or None if it stops at the node)
+        let mut down: Vec<Option<(S, Option<usize>)>> = vec![None; self.nodes.len()];
+        // Maximum path sum so far: (sum, top node of the path, left child used, right child used)
+        let mut best: Option<(S, usize, Option<usize>, Option<usize>)> = None;
+        // Default is the zero of T, as in `sum`
+        let zero = widen(T::default());
+
+        for (current_id, &key) in self.iter_postorder() {
             let node = &self.nodes[current_id];
-            let (left_max, left_sum) = self.rec_max_path_sum(node.id_left);
-            let (right_max, right_sum) = self.rec_max_path_sum(node.id_right);
-
-            let path_sum = Some(node.key + left_sum.unwrap_or(T::default()).max(right_sum.unwrap_or(T::default())));
-
-            // Compute the maximum path sum so far, comparing maximum sum of left path,
-            // maximum sum of right path and the sum of the actual path.
-            // Generate one iterator on the Option left_max and chain with:
-            // - the iterator on right_sum (generated by chain() itself)
-            // - the iterator on key + left_sum + right_sum (generated always by chain itself)
-            // Then compute the max on the resultant iterator.
-            let max_sum = left_max
-                .into_iter()
-                .chain(right_max)
-                .chain(Some(
-                    node.key + left_sum.unwrap_or(T::default()) + right_sum.unwrap_or(T::default()),
-                ))
-                .max();
-
-            return (max_sum, path_sum);
+            let key = widen(key);
+
+            // Sum of the key and the downward path of each child, keeping a child only
+            // if its downward path increases the sum. The children are dropped before
+            // the addition, so a path that is discarded anyway never makes `add` fail.
+            let mut extended = [None, None];
+            for (i, child) in [node.id_left, node.id_right].into_iter().enumerate() {
+                if let Some(id) = child {
+                    let (child_sum, _) = down[id].unwrap();
+                    if child_sum > zero {
+                        extended[i] = Some((add(key, child_sum)?, id));
+                    }
+                }
+            }
+            let [left, right] = extended;
+
+            // The downward path continues in the better of the two children
+            let next = match (left, right) {
+                (Some(left), Some(right)) if left.0 >= right.0 => Some(left),
+                (Some(_), Some(right)) => Some(right),
+                (left, right) => left.or(right),
+            };
+            down[current_id] = Some(next.map_or((key, None), |(sum, id)| (sum, Some(id))));
+
+            // The path with the current node on top joins the two downward paths
+            let sum = match (left, right) {
+                (Some((left_sum, _)), Some((_, right_id))) => add(left_sum, down[right_id].unwrap().0)?,
+                (left, right) => left.or(right).map_or(key, |(sum, _)| sum),
+            };
+            if best.is_none_or(|(best_sum, ..)| sum > best_sum) {
+                best = Some((sum, current_id, left.map(|(_, id)| id), right.map(|(_, id)| id)));
+            }
         }
-        (None, None)
+
+        let Some((sum, top, left, right)) = best else {
+            return Some(None);
+        };
+        let downward = |start: Option<usize>| std::iter::successors(start, |&id| down[id].unwrap().1);
+
+        // From the bottom of the left path up to the top node, then down the right path
+        let mut path: Vec<usize> = downward(left).collect();
+        path.reverse();
+        path.push(top);
+        path.extend(downward(right));
+
+        Some(Some((sum, path)))
     }
 }
 
@@ -404,4 +763,452 @@ mod tests {
         // Max path should be: 30 -> 15 -> 10 -> 20 -> 30
         assert_eq!(tree.max_path_sum(), Some(105));
     }
+
+    #[test]
+    fn test_remove_subtree() {
+        let mut tree = Tree::with_root(40);
+        tree.add_node(0, 30, true); // id 1
+        tree.add_node(0, 50, false); // id 2
+        tree.add_node(1, 25, true); // id 3
+        tree.add_node(1, 35, false); // id 4
+        tree.add_node(2, 45, true); // id 5
+        tree.add_node(2, 60, false); // id 6
+
+        //       40
+        //     /    \
+        //   30      50
+        //  / \     / \
+        // 25 35  45  60
+
+        assert_eq!(tree.remove_subtree(1), 3);
+
+        //   40
+        //     \
+        //      50
+        //     / \
+        //   45  60
+
+        assert_eq!(tree.sum(), 195);
+        assert!(tree.is_bst());
+        // 45 -> 50 -> 60
+        assert_eq!(tree.max_path_sum(), Some(155));
+
+        // The left slot of the root is free again and the
+        // removed slots are reused by the next insertions
+        let id = tree.add_node(0, 20, true);
+        assert!([1, 3, 4].contains(&id));
+        assert_eq!(tree.sum(), 215);
+
+        // The ids of the remaining nodes are still valid
+        tree.add_node(5, 42, true);
+        assert_eq!(tree.sum(), 257);
+        assert!(tree.is_bst());
+    }
+
+    #[test]
+    fn test_remove_leaf() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(0, 8, false); // id 2
+
+        //     10
+        //    / \
+        //   5   8
+
+        assert!(!tree.is_bst());
+        assert_eq!(tree.remove_subtree(2), 1);
+        assert!(tree.is_bst());
+        assert_eq!(tree.sum(), 15);
+    }
+
+    #[test]
+    fn test_remove_root() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true);
+        tree.add_node(0, 8, false);
+
+        assert_eq!(tree.remove_subtree(0), 3);
+
+        // The tree is empty
+        assert_eq!(tree.sum(), 0);
+        assert!(tree.is_bst());
+        assert_eq!(tree.max_path_sum(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id does not exist")]
+    fn test_remove_twice() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+
+        tree.remove_subtree(1);
+        tree.remove_subtree(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Parent node id does not exist")]
+    fn test_add_node_to_removed_parent() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+
+        tree.remove_subtree(1);
+        tree.add_node(1, 3, true);
+    }
+
+    #[test]
+    fn test_detach() {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 1, true); // id 1
+        tree.add_node(0, 1, false); // id 2
+        tree.add_node(1, 1, true); // id 3
+        tree.add_node(1, 1, false); // id 4
+        tree.add_node(4, 20, true); // id 5
+        tree.add_node(4, 25, false); // id 6
+
+        //        1
+        //      /  \
+        //     1    1
+        //    / \
+        //   1   1
+        //      / \
+        //    20  25
+
+        let subtree = tree.detach(4);
+
+        //   1
+        //  / \
+        // 20  25
+        assert_eq!(subtree.sum(), 46);
+        assert_eq!(subtree.max_path_sum(), Some(46));
+        assert!(!subtree.is_bst());
+
+        //        1
+        //      /  \
+        //     1    1
+        //    /
+        //   1
+        assert_eq!(tree.sum(), 4);
+        assert_eq!(tree.max_path_sum(), Some(4));
+
+        // The detached tree can grow on its own
+        let mut subtree = subtree;
+        subtree.add_node(1, 7, true);
+        assert_eq!(subtree.sum(), 53);
+    }
+
+    /// Builds a degenerate tree where every node is the child of the previous one
+    fn chain(n: usize, key: i64, is_left: bool) -> Tree<i64> {
+        let mut tree = Tree::with_root(key);
+        let mut last = 0;
+        for _ in 1..n {
+            last = tree.add_node(last, key, is_left);
+        }
+        tree
+    }
+
+    #[test]
+    fn test_sum_million_nodes_chain() {
+        let tree = chain(1_000_000, 3, false);
+        assert_eq!(tree.sum(), 3_000_000);
+    }
+
+    #[test]
+    fn test_is_bst_million_nodes_chain() {
+        // Equal keys are allowed by the in-order check
+        assert!(chain(1_000_000, 1, false).is_bst());
+        assert!(chain(1_000_000, 1, true).is_bst());
+
+        // A decreasing right chain is not a BST
+        let mut tree = Tree::with_root(1_000_000);
+        let mut last = 0;
+        for key in (0..1_000_000).rev() {
+            last = tree.add_node(last, key, false);
+        }
+        assert!(!tree.is_bst());
+    }
+
+    #[test]
+    fn test_max_path_sum_million_nodes_chain() {
+        assert_eq!(chain(1_000_000, 2, false).max_path_sum(), Some(2_000_000));
+        assert_eq!(chain(1_000_000, 2, true).max_path_sum(), Some(2_000_000));
+    }
+
+    #[test]
+    fn test_max_path() {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 1, true); // id 1
+        tree.add_node(0, 1, false); // id 2
+        tree.add_node(1, 1, true); // id 3
+        tree.add_node(1, 1, false); // id 4
+        tree.add_node(4, 20, true); // id 5
+        tree.add_node(4, 25, false); // id 6
+
+        //        1
+        //      /  \
+        //     1    1
+        //    / \
+        //   1   1
+        //      / \
+        //    20  25
+
+        assert_eq!(tree.max_path(), Some((46, vec![5, 4, 6])));
+    }
+
+    #[test]
+    fn test_max_path_negative_keys() {
+        let mut tree: Tree<i64> = Tree::with_root(-10);
+        tree.add_node(0, 9, true); // id 1
+        tree.add_node(0, 20, false); // id 2
+        tree.add_node(2, 15, true); // id 3
+        tree.add_node(2, 7, false); // id 4
+
+        //    -10
+        //    /  \
+        //   9    20
+        //       /  \
+        //      15   7
+
+        // The root is skipped: 15 -> 20 -> 7
+        assert_eq!(tree.max_path(), Some((42, vec![3, 2, 4])));
+        assert_eq!(tree.max_path_sum(), Some(42));
+    }
+
+    #[test]
+    fn test_max_path_skips_negative_children() {
+        let mut tree: Tree<i64> = Tree::with_root(5);
+        tree.add_node(0, -3, true); // id 1
+        tree.add_node(0, -4, false); // id 2
+
+        //     5
+        //    / \
+        //  -3   -4
+
+        // The best path is the root alone
+        assert_eq!(tree.max_path(), Some((5, vec![0])));
+
+        tree.add_node(1, 10, true); // id 3
+
+        //       5
+        //      / \
+        //    -3   -4
+        //    /
+        //   10
+
+        // 10 -> -3 -> 5
+        assert_eq!(tree.max_path(), Some((12, vec![3, 1, 0])));
+    }
+
+    #[test]
+    fn test_max_path_all_negative_keys() {
+        let mut tree: Tree<i64> = Tree::with_root(-3);
+        tree.add_node(0, -1, true); // id 1
+        tree.add_node(0, -2, false); // id 2
+        tree.add_node(1, -5, false); // id 3
+
+        // The maximum is the single node with the largest key
+        assert_eq!(tree.max_path(), Some((-1, vec![1])));
+    }
+
+    #[test]
+    fn test_max_path_empty_tree() {
+        let mut tree: Tree<i64> = Tree::with_root(-3);
+        tree.remove_subtree(0);
+        assert_eq!(tree.max_path(), None);
+    }
+
+    #[test]
+    fn test_try_add_node() {
+        let mut tree = Tree::with_root(10);
+
+        assert_eq!(tree.try_add_node(0, 5, true), Ok(1));
+        assert_eq!(
+            tree.try_add_node(0, 7, true),
+            Err(TreeError::ChildAlreadySet {
+                parent_id: 0,
+                is_left: true
+            })
+        );
+        assert_eq!(
+            tree.try_add_node(3, 7, false),
+            Err(TreeError::ParentNotFound { parent_id: 3 })
+        );
+
+        // The failed insertions don't change the tree
+        assert_eq!(tree.sum(), 15);
+        assert_eq!(tree.try_add_node(0, 7, false), Ok(2));
+    }
+
+    #[test]
+    fn test_try_remove_subtree() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(1, 3, true); // id 2
+
+        assert_eq!(tree.try_remove_subtree(1), Ok(2));
+        assert_eq!(
+            tree.try_remove_subtree(2),
+            Err(TreeError::NodeNotFound { node_id: 2 })
+        );
+        assert_eq!(
+            tree.try_add_node(1, 3, true),
+            Err(TreeError::ParentNotFound { parent_id: 1 })
+        );
+        assert!(tree.try_detach(7).is_err());
+        assert_eq!(tree.try_detach(0).map(|subtree| subtree.sum()), Ok(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Parent node has the right child already set")]
+    fn test_add_node_child_already_set() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, false);
+        tree.add_node(0, 7, false);
+    }
+
+    /// Sorted copy of the keys, the oracle of the order-statistics queries
+    fn sorted_keys(tree: &Tree<i32>) -> Vec<i32> {
+        tree.iter_inorder().map(|(_, &key)| key).collect()
+    }
+
+    #[test]
+    fn test_order_statistics() {
+        let mut tree = Tree::new();
+        for key in [50, 30, 70, 20, 40, 60, 80, 35] {
+            tree.insert(key);
+        }
+
+        //          50
+        //        /    \
+        //      30      70
+        //     /  \    /  \
+        //   20   40  60   80
+        //        /
+        //      35
+
+        assert_eq!(tree.kth_smallest(0), Some(20));
+        assert_eq!(tree.kth_smallest(3), Some(40));
+        assert_eq!(tree.kth_smallest(7), Some(80));
+        assert_eq!(tree.kth_smallest(8), None);
+
+        assert_eq!(tree.rank(&20), 0);
+        assert_eq!(tree.rank(&50), 4);
+        assert_eq!(tree.rank(&55), 5);
+        assert_eq!(tree.rank(&100), 8);
+
+        assert_eq!(tree.count_in_range(&30, &60), 5);
+        assert_eq!(tree.count_in_range(&36, &39), 0);
+        assert_eq!(tree.count_in_range(&0, &100), 8);
+        assert_eq!(tree.count_in_range(&60, &30), 0);
+    }
+
+    #[test]
+    fn test_order_statistics_after_removals() {
+        let mut tree = Tree::new();
+        tree.set_duplicate_policy(DuplicatePolicy::Right);
+        let mut generator = TreeGenerator::new(9);
+        for step in 0..2_000 {
+            let key = generator.key(&(0..=99));
+            if step % 3 == 2 {
+                tree.remove(&key);
+            } else {
+                tree.insert(key);
+            }
+
+            let sorted = sorted_keys(&tree);
+            assert_eq!(tree.kth_smallest(sorted.len() / 2), sorted.get(sorted.len() / 2).copied());
+            assert_eq!(tree.rank(&key), sorted.partition_point(|&other| other < key));
+            assert_eq!(
+                tree.count_in_range(&(key - 10), &key),
+                sorted.iter().filter(|&&other| key - 10 <= other && other <= key).count()
+            );
+        }
+    }
+
+    /// Checks the stored subtree size of every node against a count of its subtree
+    fn assert_sizes<T: Ord + std::ops::Add<Output = T> + Default + Copy>(tree: &Tree<T>) {
+        let mut sizes = vec![0; tree.nodes.len()];
+        for (id, _) in tree.iter_postorder() {
+            let node = &tree.nodes[id];
+            sizes[id] = 1 + node.id_left.map_or(0, |left| sizes[left])
+                + node.id_right.map_or(0, |right| sizes[right]);
+            assert_eq!(node.size.get(), sizes[id]);
+        }
+    }
+
+    #[test]
+    fn test_sizes_of_built_trees() {
+        // The builders count the sizes once at the end, they are never stale
+        let mut generator = TreeGenerator::new(18);
+        for shape in [Shape::Chain, Shape::Complete, Shape::RandomBinary, Shape::Height(9)] {
+            let mut tree = generator.tree(300, shape, 0i32..=99);
+            assert_sizes(&tree);
+
+            let child = tree.nodes[0].id_left.or(tree.nodes[0].id_right).unwrap();
+            let subtree = tree.detach(child);
+            assert_sizes(&tree);
+            assert_sizes(&subtree);
+
+            let mut patched = Tree::new();
+            patched.apply(&patched.diff(&tree));
+            assert_sizes(&patched);
+            patched.apply(&patched.diff(&subtree));
+            assert_sizes(&patched);
+
+            let mut versions = PersistentTree::new();
+            let version = versions.from_tree(&tree);
+            assert_sizes(&versions.to_tree(version));
+
+            // Distinct keys for the rebuild from the sequences
+            for id in 0..tree.nodes.len() {
+                tree.nodes[id].key = id as i32;
+            }
+            let rebuilt = Tree::from_pre_in(&tree.preorder_keys(), &tree.inorder_keys()).unwrap();
+            assert_sizes(&rebuilt);
+
+            let parsed: Tree<i32> = Tree::from_level_order(&tree.to_level_order()).unwrap();
+            assert_sizes(&parsed);
+        }
+    }
+
+    #[test]
+    fn test_order_statistics_add_node() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(0, 15, false); // id 2
+        tree.add_node(2, 12, true); // id 3
+
+        // `add_node` leaves the sizes stale, the first query recounts them
+        assert!(tree.sizes_stale.get());
+        assert_eq!(tree.kth_smallest(2), Some(12));
+        assert!(!tree.sizes_stale.get());
+        assert_eq!(tree.nodes[0].size.get(), 4);
+        assert_eq!(tree.nodes[2].size.get(), 2);
+        assert_eq!(tree.rank(&15), 3);
+        assert_eq!(tree.count_in_range(&5, &12), 3);
+
+        // Removing a subtree keeps the sizes up to date
+        tree.remove_subtree(3);
+        assert_eq!(tree.nodes[0].size.get(), 3);
+        assert_eq!(tree.kth_smallest(2), Some(15));
+
+        // `insert` on stale sizes leaves them to the recount
+        tree.add_node(1, 7, false);
+        tree.insert(20);
+        assert_eq!(tree.kth_smallest(4), Some(20));
+        assert_eq!(tree.rank(&10), 2);
+        assert_sizes(&tree);
+    }
+
+    #[test]
+    fn test_kth_smallest_large_bst() {
+        let mut tree = Tree::new();
+        // Keys inserted in a scrambled order, so the BST is not a chain
+        for i in 0..100_000i64 {
+            tree.insert(i * 7_919 % 100_000);
+        }
+
+        assert_eq!(tree.kth_smallest(12_345), Some(12_345));
+        assert_eq!(tree.rank(&99_999), 99_999);
+        assert_eq!(tree.count_in_range(&1_000, &1_999), 1_000);
+    }
 }
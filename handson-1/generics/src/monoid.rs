@@ -0,0 +1,218 @@
+use std::{marker::PhantomData, ops::Add};
+
+use crate::Tree;
+
+/// A set of values with an associative operation and its identity element.
+/// `combine(identity(), a) == a == combine(a, identity())` must hold, and
+/// `combine` must be associative. It doesn't need to be commutative.
+pub trait Monoid {
+    type Item;
+
+    fn identity() -> Self::Item;
+    fn combine(a: &Self::Item, b: &Self::Item) -> Self::Item;
+}
+
+/// Sum of the values, with `T::default()` as identity
+pub struct SumMonoid<T>(PhantomData<T>);
+
+impl<T: Add<Output = T> + Default + Copy> Monoid for SumMonoid<T> {
+    type Item = T;
+
+    fn identity() -> T {
+        T::default()
+    }
+
+    fn combine(a: &T, b: &T) -> T {
+        *a + *b
+    }
+}
+
+/// Number of values, map every key to 1 to get the size of the subtrees
+pub struct CountMonoid;
+
+impl Monoid for CountMonoid {
+    type Item = usize;
+
+    fn identity() -> usize {
+        0
+    }
+
+    fn combine(a: &usize, b: &usize) -> usize {
+        a + b
+    }
+}
+
+/// Minimum of the values, None is the identity
+pub struct MinMonoid<T>(PhantomData<T>);
+
+impl<T: Ord + Copy> Monoid for MinMonoid<T> {
+    type Item = Option<T>;
+
+    fn identity() -> Option<T> {
+        None
+    }
+
+    fn combine(a: &Option<T>, b: &Option<T>) -> Option<T> {
+        // None is smaller than any Some, so it can't be compared directly
+        match (a, b) {
+            (Some(a), Some(b)) => Some(*a.min(b)),
+            _ => a.or(*b),
+        }
+    }
+}
+
+/// Maximum of the values, None is the identity
+pub struct MaxMonoid<T>(PhantomData<T>);
+
+impl<T: Ord + Copy> Monoid for MaxMonoid<T> {
+    type Item = Option<T>;
+
+    fn identity() -> Option<T> {
+        None
+    }
+
+    fn combine(a: &Option<T>, b: &Option<T>) -> Option<T> {
+        // None is smaller than any Some, so it is ignored by max
+        *a.max(b)
+    }
+}
+
+impl<T> Tree<T> {
+    /// Computes the aggregate of every subtree in one post-order visit, O(n) calls
+    /// of `M::combine`. The key of each node is turned into an `M::Item` by `map`.
+    /// The aggregate of a node is `combine(combine(left, map(key)), right)`, so with
+    /// a non-commutative monoid the values are combined in in-order.
+    /// The result is indexed by node id, the ids that are not in the tree get the identity.
+    pub fn aggregate_subtrees<M: Monoid>(&self, map: impl Fn(&T) -> M::Item) -> Vec<M::Item> {
+        let mut aggregates: Vec<M::Item> = (0..self.nodes.len()).map(|_| M::identity()).collect();
+
+        for (id, key) in self.iter_postorder() {
+            let node = &self.nodes[id];
+            let mut aggregate = map(key);
+
+            // The children are visited before the node, so their aggregates are ready
+            if let Some(left) = node.id_left {
+                aggregate = M::combine(&aggregates[left], &aggregate);
+            }
+            if let Some(right) = node.id_right {
+                aggregate = M::combine(&aggregate, &aggregates[right]);
+            }
+            aggregates[id] = aggregate;
+        }
+        aggregates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(40);
+        tree.add_node(0, 30, true); // id 1
+        tree.add_node(0, 50, false); // id 2
+        tree.add_node(1, 25, true); // id 3
+        tree.add_node(1, 35, false); // id 4
+        tree.add_node(2, -45, true); // id 5
+
+        //        40
+        //      /    \
+        //    30      50
+        //   /  \    /
+        //  25  35 -45
+        tree
+    }
+
+    #[test]
+    fn test_subtree_sums() {
+        let tree = tree();
+        let sums = tree.aggregate_subtrees::<SumMonoid<i32>>(|&key| key);
+
+        assert_eq!(sums, vec![135, 90, 5, 25, 35, -45]);
+        assert_eq!(sums[0], tree.sum());
+    }
+
+    #[test]
+    fn test_subtree_sizes() {
+        let sizes = tree().aggregate_subtrees::<CountMonoid>(|_| 1);
+        assert_eq!(sizes, vec![6, 3, 2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_subtree_min_max() {
+        let tree = tree();
+
+        let min = tree.aggregate_subtrees::<MinMonoid<i32>>(|&key| Some(key));
+        assert_eq!(
+            min,
+            vec![
+                Some(-45),
+                Some(25),
+                Some(-45),
+                Some(25),
+                Some(35),
+                Some(-45)
+            ]
+        );
+
+        let max = tree.aggregate_subtrees::<MaxMonoid<i32>>(|&key| Some(key));
+        assert_eq!(
+            max,
+            vec![Some(50), Some(35), Some(50), Some(25), Some(35), Some(-45)]
+        );
+    }
+
+    /// Polynomial hash of the in-order sequence: (hash, base^length)
+    struct SequenceHash;
+
+    impl Monoid for SequenceHash {
+        type Item = (u64, u64);
+
+        fn identity() -> (u64, u64) {
+            (0, 1)
+        }
+
+        fn combine(a: &(u64, u64), b: &(u64, u64)) -> (u64, u64) {
+            (
+                a.0.wrapping_mul(b.1).wrapping_add(b.0),
+                a.1.wrapping_mul(b.1),
+            )
+        }
+    }
+
+    #[test]
+    fn test_non_commutative_monoid() {
+        let tree = tree();
+        let hashes = tree.aggregate_subtrees::<SequenceHash>(|&key| (key as u64, 31));
+
+        // The hash of the whole tree is the hash of the in-order sequence
+        let expected = tree
+            .iter_inorder()
+            .fold(SequenceHash::identity(), |hash, (_, &key)| {
+                SequenceHash::combine(&hash, &(key as u64, 31))
+            });
+        assert_eq!(hashes[0], expected);
+        assert_ne!(hashes[1], hashes[2]);
+    }
+
+    #[test]
+    fn test_removed_nodes() {
+        let mut tree = tree();
+        tree.remove_subtree(1);
+
+        let sizes = tree.aggregate_subtrees::<CountMonoid>(|_| 1);
+        assert_eq!(sizes, vec![3, 0, 2, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_million_nodes_chain() {
+        let mut tree = Tree::with_root(1i64);
+        for id in 0..999_999 {
+            tree.add_node(id, 1, true);
+        }
+
+        let sums = tree.aggregate_subtrees::<SumMonoid<i64>>(|&key| key);
+        assert_eq!(sums[0], 1_000_000);
+        assert_eq!(sums[999_999], 1);
+    }
+}
@@ -0,0 +1,515 @@
+use std::{collections::VecDeque, ops::Add};
+
+use crate::{Tree, TreeError};
+
+struct NaryNode<T> {
+    key: T,
+    // Ids of the children, in order
+    children: Vec<usize>,
+}
+
+/// Rooted ordered tree where every node has any number of children.
+/// The nodes are stored in an arena like in `Tree`, the ids are the indices.
+pub struct NaryTree<T> {
+    nodes: Vec<NaryNode<T>>,
+    // None for the empty tree
+    root: Option<usize>,
+}
+
+impl<T> Default for NaryTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> NaryTree<T> {
+    /// Returns an empty tree, the root can be added with `set_root`
+    pub fn new() -> Self {
+        NaryTree {
+            nodes: Vec::new(),
+            root: None,
+        }
+    }
+
+    pub fn with_root(key: T) -> Self {
+        NaryTree {
+            nodes: vec![NaryNode {
+                key,
+                children: Vec::new(),
+            }],
+            root: Some(0),
+        }
+    }
+
+    /// Adds the root with `key` to an empty tree and returns its id.
+    ///
+    /// # Panics
+    /// Panics if the tree has a root already. `try_set_root` returns the error instead.
+    pub fn set_root(&mut self, key: T) -> usize {
+        self.try_set_root(key)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Adds the root with `key` like `set_root`, or returns an error if the tree
+    /// has a root already
+    pub fn try_set_root(&mut self, key: T) -> Result<usize, TreeError> {
+        if self.root.is_some() {
+            return Err(TreeError::RootAlreadySet);
+        }
+        self.nodes.push(NaryNode {
+            key,
+            children: Vec::new(),
+        });
+        self.root = Some(self.nodes.len() - 1);
+        Ok(self.nodes.len() - 1)
+    }
+
+    /// Adds a node with `key` as the last child of `parent_id` and returns its id.
+    ///
+    /// # Panics
+    /// Panics if the `parent_id` does not exist. `try_add_child` returns the error instead.
+    pub fn add_child(&mut self, parent_id: usize, key: T) -> usize {
+        self.try_add_child(parent_id, key)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Adds a node with `key` as the last child of `parent_id` like `add_child`,
+    /// or returns an error if the `parent_id` does not exist.
+    pub fn try_add_child(&mut self, parent_id: usize, key: T) -> Result<usize, TreeError> {
+        if parent_id >= self.nodes.len() {
+            return Err(TreeError::ParentNotFound { parent_id });
+        }
+        self.nodes.push(NaryNode {
+            key,
+            children: Vec::new(),
+        });
+        let child_id = self.nodes.len() - 1;
+        self.nodes[parent_id].children.push(child_id);
+        Ok(child_id)
+    }
+
+    /// Returns the id of the root, None if the tree is empty
+    pub fn root(&self) -> Option<usize> {
+        self.root
+    }
+
+    /// Returns the number of nodes in the tree
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns if the tree has no nodes
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the key of `node_id`
+    ///
+    /// # Panics
+    /// Panics if the `node_id` does not exist.
+    pub fn key(&self, node_id: usize) -> &T {
+        &self.nodes[node_id].key
+    }
+
+    /// Returns the ids of the children of `node_id`, in order
+    ///
+    /// # Panics
+    /// Panics if the `node_id` does not exist.
+    pub fn children(&self, node_id: usize) -> &[usize] {
+        &self.nodes[node_id].children
+    }
+
+    /// Returns an iterator over the nodes in pre-order (node, then the children in order).
+    /// Each item is the pair `(node_id, &key)`.
+    pub fn iter_preorder(&self) -> NaryPreorder<'_, T> {
+        NaryPreorder {
+            tree: self,
+            stack: self.root.into_iter().collect(),
+        }
+    }
+
+    /// Returns an iterator over the nodes in post-order (the children in order, then node).
+    /// Each item is the pair `(node_id, &key)`.
+    pub fn iter_postorder(&self) -> NaryPostorder<'_, T> {
+        NaryPostorder {
+            tree: self,
+            stack: self.root.map(|id| (id, false)).into_iter().collect(),
+        }
+    }
+
+    /// Returns an iterator over the nodes level by level, from left to right.
+    /// Each item is the pair `(node_id, &key)`.
+    pub fn iter_level_order(&self) -> NaryLevelOrder<'_, T> {
+        NaryLevelOrder {
+            tree: self,
+            queue: self.root.into_iter().collect(),
+        }
+    }
+
+    /// Returns the pair `(node_id, &key)` yielded by the iterators
+    fn item(&self, node_id: usize) -> (usize, &T) {
+        (node_id, &self.nodes[node_id].key)
+    }
+}
+
+impl<T: Ord + Add<Output = T> + Default + Copy> NaryTree<T> {
+    /// Returns the sum of all the keys in the tree
+    pub fn sum(&self) -> T {
+        self.iter_preorder()
+            .fold(T::default(), |sum, (_, &key)| sum + key)
+    }
+
+    /// Returns the maximum path sum, like `Tree::max_path_sum`.
+    /// If the tree is empty, it returns None.
+    /// The path with a node on top joins the two best downward paths of its
+    /// children, and it goes down into a child only if that increases the sum.
+    pub fn max_path_sum(&self) -> Option<T> {
+        // Maximum sum of a path that goes down from every node
+        let mut down: Vec<T> = vec![T::default(); self.nodes.len()];
+        let mut best: Option<T> = None;
+
+        for (current_id, &key) in self.iter_postorder() {
+            // The two children with the best downward paths, (extended sum, child id)
+            let mut first: Option<(T, usize)> = None;
+            let mut second: Option<(T, usize)> = None;
+            for &child in &self.nodes[current_id].children {
+                let extended = key + down[child];
+                // Keep a child only if its downward path increases the sum
+                if extended <= key {
+                    continue;
+                }
+                if first.is_none_or(|(sum, _)| extended > sum) {
+                    second = first;
+                    first = Some((extended, child));
+                } else if second.is_none_or(|(sum, _)| extended > sum) {
+                    second = Some((extended, child));
+                }
+            }
+
+            down[current_id] = first.map_or(key, |(sum, _)| sum);
+            let sum = match (first, second) {
+                (Some((sum, _)), Some((_, second_id))) => sum + down[second_id],
+                (first, _) => first.map_or(key, |(sum, _)| sum),
+            };
+            if best.is_none_or(|best| sum > best) {
+                best = Some(sum);
+            }
+        }
+        best
+    }
+
+    /// Returns the left-child/right-sibling encoding of the tree as a binary tree:
+    /// the left child of a node is its first child, the right child is its next sibling.
+    /// The nodes of the binary tree are numbered in pre-order, the root has id 0.
+    pub fn to_binary(&self) -> Tree<T> {
+        let Some(root) = self.root else {
+            return Tree::new();
+        };
+        let mut binary = Tree::with_root(self.nodes[root].key);
+
+        // Triples of (nary parent, index of the child, parent in the binary tree).
+        // The first child is a left child, the other ones are right children
+        // of their previous sibling.
+        let mut stack = vec![];
+        if !self.nodes[root].children.is_empty() {
+            stack.push((root, 0, 0));
+        }
+        while let Some((parent, index, binary_parent)) = stack.pop() {
+            let siblings = &self.nodes[parent].children;
+            let id = siblings[index];
+            let binary_id = binary.attach(binary_parent, self.nodes[id].key, index == 0);
+
+            // Push the next sibling first, so that the children are visited first
+            if index + 1 < siblings.len() {
+                stack.push((parent, index + 1, binary_id));
+            }
+            if !self.nodes[id].children.is_empty() {
+                stack.push((id, 0, binary_id));
+            }
+        }
+        binary.count_attached(0);
+        binary
+    }
+}
+
+impl<T: Copy> Tree<T> {
+    /// Decodes the left-child/right-sibling encoding made by `NaryTree::to_binary`:
+    /// the left child of a node is its first child, the right child is its next sibling.
+    /// It returns None if the root has a right child, because the root has no siblings.
+    /// The nodes of the n-ary tree are numbered in pre-order, the root has id 0.
+    pub fn to_nary(&self) -> Option<NaryTree<T>> {
+        let Some(root) = self.root else {
+            return Some(NaryTree::new());
+        };
+        if self.nodes[root].id_right.is_some() {
+            return None;
+        }
+        let mut nary = NaryTree::with_root(self.nodes[root].key);
+
+        // Pairs of (binary id, nary parent). A left child is a child of its binary
+        // parent, a right child is a sibling, so it has the same nary parent.
+        // The pre-order of the binary tree adds the siblings in order.
+        let mut stack: Vec<(usize, usize)> = self.nodes[root]
+            .id_left
+            .map(|left| (left, 0))
+            .into_iter()
+            .collect();
+        while let Some((id, nary_parent)) = stack.pop() {
+            let node = &self.nodes[id];
+            let nary_id = nary.add_child(nary_parent, node.key);
+
+            stack.extend(node.id_right.map(|right| (right, nary_parent)));
+            stack.extend(node.id_left.map(|left| (left, nary_id)));
+        }
+        Some(nary)
+    }
+}
+
+/// Pre-order iterator returned by `NaryTree::iter_preorder`
+pub struct NaryPreorder<'a, T> {
+    tree: &'a NaryTree<T>,
+    stack: Vec<usize>,
+}
+
+impl<'a, T> Iterator for NaryPreorder<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+
+        // Push the children backward, so that the first one is visited first
+        self.stack.extend(self.tree.nodes[id].children.iter().rev());
+
+        Some(self.tree.item(id))
+    }
+}
+
+/// Post-order iterator returned by `NaryTree::iter_postorder`
+pub struct NaryPostorder<'a, T> {
+    tree: &'a NaryTree<T>,
+    // Pairs of (node_id, children already pushed)
+    stack: Vec<(usize, bool)>,
+}
+
+impl<'a, T> Iterator for NaryPostorder<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((id, expanded)) = self.stack.pop() {
+            if expanded {
+                return Some(self.tree.item(id));
+            }
+
+            // The node is yielded after all of its children
+            self.stack.push((id, true));
+            self.stack.extend(
+                self.tree.nodes[id]
+                    .children
+                    .iter()
+                    .rev()
+                    .map(|&child| (child, false)),
+            );
+        }
+        None
+    }
+}
+
+/// Level-order iterator returned by `NaryTree::iter_level_order`
+pub struct NaryLevelOrder<'a, T> {
+    tree: &'a NaryTree<T>,
+    queue: VecDeque<usize>,
+}
+
+impl<'a, T> Iterator for NaryLevelOrder<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.queue.pop_front()?;
+        self.queue.extend(&self.tree.nodes[id].children);
+        Some(self.tree.item(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> NaryTree<i32> {
+        let mut tree = NaryTree::with_root(1);
+        tree.add_child(0, 2); // id 1
+        tree.add_child(0, 3); // id 2
+        tree.add_child(0, 4); // id 3
+        tree.add_child(1, 5); // id 4
+        tree.add_child(1, 6); // id 5
+        tree.add_child(3, 7); // id 6
+        tree.add_child(6, 8); // id 7
+
+        //         1
+        //      /  |  \
+        //     2   3   4
+        //    / \      |
+        //   5   6     7
+        //             |
+        //             8
+        tree
+    }
+
+    fn keys<'a>(iter: impl Iterator<Item = (usize, &'a i32)>) -> Vec<i32> {
+        iter.map(|(_, &key)| key).collect()
+    }
+
+    #[test]
+    fn test_traversals() {
+        let tree = tree();
+
+        assert_eq!(keys(tree.iter_preorder()), vec![1, 2, 5, 6, 3, 4, 7, 8]);
+        assert_eq!(keys(tree.iter_postorder()), vec![5, 6, 2, 3, 8, 7, 4, 1]);
+        assert_eq!(keys(tree.iter_level_order()), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(NaryTree::<i32>::new().iter_preorder().count(), 0);
+    }
+
+    #[test]
+    fn test_sum() {
+        assert_eq!(tree().sum(), 36);
+        assert_eq!(NaryTree::<i32>::new().sum(), 0);
+    }
+
+    #[test]
+    fn test_max_path_sum() {
+        // 6 - 2 - 1 - 4 - 7 - 8
+        assert_eq!(tree().max_path_sum(), Some(28));
+        assert_eq!(NaryTree::<i32>::new().max_path_sum(), None);
+
+        let mut tree = NaryTree::with_root(-10);
+        tree.add_child(0, -3);
+        tree.add_child(0, -5);
+        assert_eq!(tree.max_path_sum(), Some(-3));
+
+        // The two best of four children are joined
+        let mut tree = NaryTree::with_root(1);
+        for key in [4, -2, 9, 6] {
+            tree.add_child(0, key);
+        }
+        assert_eq!(tree.max_path_sum(), Some(16));
+    }
+
+    #[test]
+    fn test_max_path_sum_matches_binary() {
+        // A binary tree seen as an n-ary tree, every node with at most two children
+        let mut nary = NaryTree::with_root(10);
+        nary.add_child(0, 2);
+        nary.add_child(0, 10);
+        nary.add_child(1, 20);
+        nary.add_child(1, 1);
+        nary.add_child(2, -25);
+        nary.add_child(5, 3);
+        nary.add_child(5, 4);
+
+        let mut binary = Tree::with_root(10);
+        binary.add_node(0, 2, true);
+        binary.add_node(0, 10, false);
+        binary.add_node(1, 20, true);
+        binary.add_node(1, 1, false);
+        binary.add_node(2, -25, false);
+        binary.add_node(5, 3, true);
+        binary.add_node(5, 4, false);
+
+        assert_eq!(nary.max_path_sum(), binary.max_path_sum());
+    }
+
+    #[test]
+    fn test_to_binary() {
+        let binary = tree().to_binary();
+
+        //   1
+        //  /
+        // 2 ------- 3 - 4
+        // |             |
+        // 5 - 6         7
+        //               |
+        //               8
+        assert_eq!(
+            binary.to_level_order(),
+            "[1,2,null,5,3,null,6,null,4,null,null,7,null,8]"
+        );
+        assert_eq!(binary.sum(), 36);
+        assert!(NaryTree::<i32>::new().to_binary().is_empty());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tree = tree();
+        let back = tree.to_binary().to_nary().unwrap();
+
+        assert_eq!(keys(back.iter_preorder()), keys(tree.iter_preorder()));
+        assert_eq!(keys(back.iter_level_order()), keys(tree.iter_level_order()));
+        assert_eq!(back.children(0).len(), 3);
+        assert_eq!(back.max_path_sum(), tree.max_path_sum());
+    }
+
+    #[test]
+    fn test_to_nary() {
+        let mut binary = Tree::with_root(1);
+        binary.add_node(0, 2, true);
+        binary.add_node(1, 3, false);
+        binary.add_node(1, 4, true);
+
+        // 2 and 3 are children of 1, 4 is a child of 2
+        let nary = binary.to_nary().unwrap();
+        assert_eq!(keys(nary.iter_level_order()), vec![1, 2, 3, 4]);
+        assert_eq!(nary.children(0).len(), 2);
+
+        // The root can't have siblings
+        binary.add_node(0, 5, false);
+        assert!(binary.to_nary().is_none());
+        assert!(Tree::<i32>::new().to_nary().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_missing_parent() {
+        let mut tree = tree();
+        assert_eq!(
+            tree.try_add_child(8, 0),
+            Err(TreeError::ParentNotFound { parent_id: 8 })
+        );
+    }
+
+    #[test]
+    fn test_set_root() {
+        let mut tree = NaryTree::new();
+        assert_eq!(
+            tree.try_add_child(0, 1),
+            Err(TreeError::ParentNotFound { parent_id: 0 })
+        );
+
+        let root = tree.set_root(1);
+        tree.add_child(root, 2);
+        tree.add_child(root, 3);
+        assert_eq!(tree.root(), Some(root));
+        assert_eq!(tree.sum(), 6);
+        assert_eq!(tree.to_binary().to_level_order(), "[1,2,null,null,3]");
+
+        assert_eq!(tree.try_set_root(4), Err(TreeError::RootAlreadySet));
+        assert_eq!(tree.len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "The tree has a root already")]
+    fn test_set_root_twice() {
+        tree().set_root(0);
+    }
+
+    #[test]
+    fn test_wide_tree() {
+        // A root with a million children becomes a chain of right children
+        let mut tree = NaryTree::with_root(0i64);
+        for key in 1..1_000_000 {
+            tree.add_child(0, key);
+        }
+        let binary = tree.to_binary();
+
+        assert_eq!(binary.sum(), 499_999_500_000);
+        assert_eq!(tree.max_path_sum(), Some(999_999 + 999_998));
+        assert_eq!(binary.to_nary().unwrap().sum(), tree.sum());
+    }
+}
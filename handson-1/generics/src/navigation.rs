@@ -0,0 +1,258 @@
+use crate::{Node, Tree, TreeError};
+
+/// Typed id of a node, so that it can't be mixed up with a key or an index.
+/// It converts from and to the raw `usize` ids returned by `add_node` and `insert`.
+/// An id is valid only for the tree that returned it, and only until its node is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Returns the raw id, the index of the node in the arena
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl From<usize> for NodeId {
+    fn from(id: usize) -> Self {
+        NodeId(id)
+    }
+}
+
+impl From<NodeId> for usize {
+    fn from(id: NodeId) -> Self {
+        id.0
+    }
+}
+
+/// Navigation of the tree through the stored child and parent links.
+/// Every method takes O(1).
+/// The methods that take a `NodeId` panic if the node is not in the tree,
+/// `contains_node` tells if it is.
+impl<T> Tree<T> {
+    /// Returns the id of the root, None if the tree is empty
+    pub fn root(&self) -> Option<NodeId> {
+        self.root.map(NodeId)
+    }
+
+    /// Returns the number of nodes in the tree
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    /// Returns if the tree has no nodes
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns if `id` is the id of a node currently in the tree
+    pub fn contains_node(&self, id: NodeId) -> bool {
+        self.nodes.get(id.0).is_some_and(|node| node.alive)
+    }
+
+    /// Returns the parent of `id`, None for the root
+    ///
+    /// # Panics
+    /// Panics if `id` is not in the tree.
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).id_parent.map(NodeId)
+    }
+
+    /// Returns the left child of `id`
+    ///
+    /// # Panics
+    /// Panics if `id` is not in the tree.
+    pub fn left(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).id_left.map(NodeId)
+    }
+
+    /// Returns the right child of `id`
+    ///
+    /// # Panics
+    /// Panics if `id` is not in the tree.
+    pub fn right(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).id_right.map(NodeId)
+    }
+
+    /// Returns the other child of the parent of `id`, None for the root
+    /// and for an only child
+    ///
+    /// # Panics
+    /// Panics if `id` is not in the tree.
+    pub fn sibling(&self, id: NodeId) -> Option<NodeId> {
+        let parent = &self.nodes[self.node(id).id_parent?];
+        if parent.id_left == Some(id.0) {
+            parent.id_right.map(NodeId)
+        } else {
+            parent.id_left.map(NodeId)
+        }
+    }
+
+    /// Returns the key of `id`
+    ///
+    /// # Panics
+    /// Panics if `id` is not in the tree.
+    pub fn key(&self, id: NodeId) -> &T {
+        &self.node(id).key
+    }
+
+    /// Returns a mutable reference to the key of `id`.
+    /// Changing the key can break the BST property that `insert` and `remove` rely on.
+    ///
+    /// # Panics
+    /// Panics if `id` is not in the tree.
+    pub fn key_mut(&mut self, id: NodeId) -> &mut T {
+        self.node(id);
+        &mut self.nodes[id.0].key
+    }
+
+    /// Returns if `id` has no children
+    ///
+    /// # Panics
+    /// Panics if `id` is not in the tree.
+    pub fn is_leaf(&self, id: NodeId) -> bool {
+        let node = self.node(id);
+        node.id_left.is_none() && node.id_right.is_none()
+    }
+
+    /// Returns the node of `id`, or panics if it is not in the tree
+    fn node(&self, id: NodeId) -> &Node<T> {
+        if !self.contains_node(id) {
+            panic!("{}", TreeError::NodeNotFound { node_id: id.0 });
+        }
+        &self.nodes[id.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(0, 15, false); // id 2
+        tree.add_node(1, 3, true); // id 3
+        tree.add_node(2, 20, false); // id 4
+
+        //       10
+        //      /  \
+        //     5    15
+        //    /       \
+        //   3         20
+        tree
+    }
+
+    fn id(id: usize) -> NodeId {
+        NodeId::from(id)
+    }
+
+    #[test]
+    fn test_navigation() {
+        let tree = tree();
+        let root = tree.root().unwrap();
+
+        assert_eq!(root, id(0));
+        assert_eq!(tree.parent(root), None);
+        assert_eq!(tree.left(root), Some(id(1)));
+        assert_eq!(tree.right(root), Some(id(2)));
+        assert_eq!(tree.parent(id(4)), Some(id(2)));
+        assert_eq!(tree.left(id(2)), None);
+        assert_eq!(*tree.key(id(3)), 3);
+    }
+
+    #[test]
+    fn test_sibling() {
+        let tree = tree();
+
+        assert_eq!(tree.sibling(id(1)), Some(id(2)));
+        assert_eq!(tree.sibling(id(2)), Some(id(1)));
+        assert_eq!(tree.sibling(id(3)), None);
+        assert_eq!(tree.sibling(id(0)), None);
+    }
+
+    #[test]
+    fn test_is_leaf_and_len() {
+        let mut tree = tree();
+
+        assert!(tree.is_leaf(id(3)));
+        assert!(!tree.is_leaf(id(1)));
+        assert_eq!(tree.len(), 5);
+
+        tree.remove_subtree(3);
+        assert!(tree.is_leaf(id(1)));
+        assert_eq!(tree.len(), 4);
+
+        tree.remove_subtree(0);
+        assert_eq!(tree.len(), 0);
+        assert!(tree.is_empty());
+        assert_eq!(tree.root(), None);
+    }
+
+    #[test]
+    fn test_key_mut() {
+        let mut tree = tree();
+        *tree.key_mut(id(4)) += 5;
+
+        assert_eq!(*tree.key(id(4)), 25);
+        assert_eq!(tree.sum(), 58);
+    }
+
+    #[test]
+    fn test_walk_to_root() {
+        let mut tree = Tree::with_root(0);
+        let mut last = 0;
+        for key in 1..100 {
+            last = tree.add_node(last, key, key % 2 == 0);
+        }
+
+        let mut path = vec![];
+        let mut current = Some(NodeId::from(last));
+        while let Some(node) = current {
+            path.push(*tree.key(node));
+            current = tree.parent(node);
+        }
+        assert_eq!(path, (0..100).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_parent_links_after_edits() {
+        let mut tree = tree();
+        tree.remove_subtree(1);
+
+        // The slot of id 3 is reused by the new node, with a new parent
+        let reused = tree.add_node(4, 30, true);
+        assert_eq!(reused, 3);
+        assert_eq!(tree.parent(id(3)), Some(id(4)));
+        assert!(!tree.contains_node(id(1)));
+
+        // Removing a BST node moves its child under its parent
+        let mut bst = Tree::new();
+        for key in [10, 5, 3, 4] {
+            bst.insert(key);
+        }
+        bst.remove(&5);
+        let three = bst.left(bst.root().unwrap()).unwrap();
+        assert_eq!(*bst.key(three), 3);
+        assert_eq!(bst.parent(three), bst.root());
+
+        // Removing the root of a chain makes its child the root
+        bst.remove(&10);
+        assert_eq!(bst.parent(bst.root().unwrap()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id does not exist")]
+    fn test_removed_node() {
+        let mut tree = tree();
+        tree.remove_subtree(2);
+        tree.key(id(4));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let raw: usize = id(7).into();
+        assert_eq!(raw, 7);
+        assert_eq!(id(7).index(), 7);
+    }
+}
@@ -0,0 +1,201 @@
+use std::{fmt, ops::Add};
+
+use crate::Tree;
+
+/// Error returned by the checked aggregates when the result doesn't fit in the key type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowError;
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The sum of the keys overflows")
+    }
+}
+
+impl std::error::Error for OverflowError {}
+
+/// Additions that detect or avoid the overflow, implemented for the primitive integers.
+/// The standard library has the same methods on each integer type, but no trait for them.
+pub trait OverflowAdd: Sized {
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn saturating_add(self, other: Self) -> Self;
+    fn overflowing_add(self, other: Self) -> (Self, bool);
+}
+
+macro_rules! impl_overflow_add {
+    ($($t:ty),*) => {
+        $(
+            impl OverflowAdd for $t {
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$t>::checked_add(self, other)
+                }
+
+                fn saturating_add(self, other: Self) -> Self {
+                    <$t>::saturating_add(self, other)
+                }
+
+                fn overflowing_add(self, other: Self) -> (Self, bool) {
+                    <$t>::overflowing_add(self, other)
+                }
+            }
+        )*
+    };
+}
+
+impl_overflow_add!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl<T: Ord + Add<Output = T> + Default + Copy> Tree<T> {
+    /// Returns the sum of all the keys, each converted to the wider type `S` before
+    /// the addition, e.g. `sum_into::<u64>()` on a `Tree<u32>`.
+    pub fn sum_into<S: From<T> + Add<Output = S> + Default>(&self) -> S {
+        self.iter_preorder()
+            .fold(S::default(), |sum, (_, &key)| sum + S::from(key))
+    }
+
+    /// Returns the maximum path sum like `max_path_sum`, with the keys converted
+    /// to the wider type `S` before the addition.
+    pub fn max_path_sum_into<S: From<T> + Add<Output = S> + Ord + Copy>(&self) -> Option<S> {
+        self.fold_max_path(S::from, |a, b| Some(a + b))
+            .flatten()
+            .map(|(sum, _)| sum)
+    }
+}
+
+impl<T: Ord + Add<Output = T> + Default + Copy + OverflowAdd> Tree<T> {
+    /// Returns the sum of all the keys, or an error if it overflows `T`.
+    /// Only the total is checked: with signed keys a partial sum can go out of
+    /// the bounds of `T` and come back, whatever the order of the additions.
+    pub fn checked_sum(&self) -> Result<T, OverflowError> {
+        // The partial sums wrap around, a positive key can only wrap up and a
+        // negative key down. The total fits in `T` iff the wraps cancel out.
+        let mut wraps: isize = 0;
+        let mut sum = T::default();
+        for (_, &key) in self.iter_preorder() {
+            let (next, wrapped) = sum.overflowing_add(key);
+            if wrapped {
+                wraps += if key < T::default() { -1 } else { 1 };
+            }
+            sum = next;
+        }
+        if wraps == 0 {
+            Ok(sum)
+        } else {
+            Err(OverflowError)
+        }
+    }
+
+    /// Returns the maximum path sum (None if the tree is empty), or an error
+    /// if the sum of any candidate path overflows `T`, even one that is not the best
+    pub fn checked_max_path_sum(&self) -> Result<Option<T>, OverflowError> {
+        self.fold_max_path(|key| key, T::checked_add)
+            .map(|path| path.map(|(sum, _)| sum))
+            .ok_or(OverflowError)
+    }
+
+    /// Returns the sum of all the keys, clamped to the bounds of `T`.
+    /// With signed keys the partial sums are clamped too, so the result depends
+    /// on the order of the additions once a bound is reached.
+    pub fn saturating_sum(&self) -> T {
+        self.iter_preorder()
+            .fold(T::default(), |sum, (_, &key)| sum.saturating_add(key))
+    }
+
+    /// Returns the maximum path sum, with the partial sums clamped to the bounds of `T`
+    pub fn saturating_max_path_sum(&self) -> Option<T> {
+        self.fold_max_path(|key| key, |a, b| Some(a.saturating_add(b)))
+            .flatten()
+            .map(|(sum, _)| sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn large_tree() -> Tree<u32> {
+        let mut tree = Tree::with_root(u32::MAX / 2);
+        tree.add_node(0, u32::MAX / 2, true); // id 1
+        tree.add_node(0, 10, false); // id 2
+
+        //      MAX/2
+        //      /   \
+        //   MAX/2   10
+        tree
+    }
+
+    #[test]
+    fn test_checked_sum() {
+        let tree = large_tree();
+        assert_eq!(tree.checked_sum(), Err(OverflowError));
+
+        let mut tree = Tree::with_root(10u32);
+        tree.add_node(0, 5, true);
+        assert_eq!(tree.checked_sum(), Ok(15));
+    }
+
+    #[test]
+    fn test_checked_sum_partial_overflow() {
+        // The pre-order 100 + 100 overflows i8, the total 100 doesn't
+        let mut tree = Tree::with_root(100i8);
+        tree.add_node(0, 100, true); // id 1
+        tree.add_node(0, -100, false); // id 2
+        assert_eq!(tree.checked_sum(), Ok(100));
+
+        tree.nodes[2].key = -50;
+        assert_eq!(tree.checked_sum(), Err(OverflowError));
+
+        let mut tree = Tree::with_root(-100i8);
+        tree.add_node(0, -100, true); // id 1
+        tree.add_node(0, 100, false); // id 2
+        assert_eq!(tree.checked_sum(), Ok(-100));
+
+        // The partial sums wrap down once and up twice, the total 154 doesn't fit
+        tree.add_node(1, 127, true); // id 3
+        tree.add_node(1, 127, false); // id 4
+        assert_eq!(tree.checked_sum(), Err(OverflowError));
+    }
+
+    #[test]
+    fn test_checked_max_path_sum() {
+        let tree = large_tree();
+        assert_eq!(tree.checked_max_path_sum(), Err(OverflowError));
+
+        let mut tree = Tree::with_root(u32::MAX / 2);
+        tree.add_node(0, u32::MAX / 2, true);
+        assert_eq!(tree.checked_max_path_sum(), Ok(Some(u32::MAX - 1)));
+
+        tree.remove_subtree(0);
+        assert_eq!(tree.checked_max_path_sum(), Ok(None));
+    }
+
+    #[test]
+    fn test_checked_negative_keys() {
+        let mut tree = Tree::with_root(i8::MIN);
+        tree.add_node(0, -1, true);
+        assert_eq!(tree.checked_sum(), Err(OverflowError));
+        // The best path is the child alone, the path through both nodes is never
+        // added because the child doesn't increase the sum of the root
+        assert_eq!(tree.checked_max_path_sum(), Ok(Some(-1)));
+        assert_eq!(tree.saturating_max_path_sum(), Some(-1));
+    }
+
+    #[test]
+    fn test_saturating() {
+        let tree = large_tree();
+        assert_eq!(tree.saturating_sum(), u32::MAX);
+        assert_eq!(tree.saturating_max_path_sum(), Some(u32::MAX));
+    }
+
+    #[test]
+    fn test_wide_accumulator() {
+        let tree = large_tree();
+        let half = (u32::MAX / 2) as u64;
+
+        assert_eq!(tree.sum_into::<u64>(), 2 * half + 10);
+        assert_eq!(tree.max_path_sum_into::<u64>(), Some(2 * half + 10));
+
+        let mut tree = Tree::with_root(i32::MIN);
+        tree.add_node(0, i32::MIN, true);
+        assert_eq!(tree.sum_into::<i64>(), 2 * i32::MIN as i64);
+    }
+}
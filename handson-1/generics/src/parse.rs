@@ -0,0 +1,417 @@
+use std::{collections::VecDeque, fmt, ops::Add, str::FromStr};
+
+use crate::{Tree, TreeError};
+
+/// Error returned when a tree can't be built from its textual representation.
+/// `line` is the 1-based line of the edge-list input, `position` is the
+/// 0-based index of the token in the level-order input.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseTreeError {
+    /// The input ended before all the declared nodes were read
+    UnexpectedEnd,
+    /// There is more input after the last declared node
+    TrailingInput { line: usize },
+    /// A line doesn't have the expected number of tokens
+    WrongTokenCount { line: usize },
+    /// A token of the edge list is not a valid number, key or side
+    BadLineToken { line: usize, token: String },
+    /// A token of the level-order list is empty or not a valid key
+    BadToken { position: usize, token: String },
+    /// The parent of a node is not in the tree yet
+    MissingParent { line: usize, parent: usize },
+    /// The parent has the child on that side already set
+    ChildAlreadySet {
+        line: usize,
+        parent: usize,
+        is_left: bool,
+    },
+    /// The level-order list is not enclosed in square brackets
+    MissingBrackets,
+    /// A level-order node has no free child slot left to hang from
+    OrphanNode { position: usize },
+}
+
+impl fmt::Display for ParseTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseTreeError::UnexpectedEnd => write!(f, "Unexpected end of input"),
+            ParseTreeError::TrailingInput { line } => {
+                write!(f, "Line {}: unexpected input after the last node", line)
+            }
+            ParseTreeError::WrongTokenCount { line } => {
+                write!(f, "Line {}: wrong number of tokens", line)
+            }
+            ParseTreeError::BadLineToken { line, token } => {
+                write!(f, "Line {}: invalid token '{}'", line, token)
+            }
+            ParseTreeError::BadToken { position, token } => {
+                write!(f, "Position {}: invalid token '{}'", position, token)
+            }
+            ParseTreeError::MissingParent { line, parent } => {
+                write!(f, "Line {}: parent node {} does not exist", line, parent)
+            }
+            ParseTreeError::ChildAlreadySet {
+                line,
+                parent,
+                is_left,
+            } => write!(
+                f,
+                "Line {}: parent node {} has the {} child already set",
+                line,
+                parent,
+                if *is_left { "left" } else { "right" }
+            ),
+            ParseTreeError::MissingBrackets => {
+                write!(f, "The level-order list must be enclosed in brackets")
+            }
+            ParseTreeError::OrphanNode { position } => {
+                write!(f, "Position {}: the node has no parent", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseTreeError {}
+
+impl<T: Ord + Add<Output = T> + Default + Copy + FromStr> Tree<T> {
+    /// Builds a tree from an edge list with the following format:
+    /// - the first line contains `n`, the number of nodes
+    /// - the second line contains the key of the root
+    /// - each of the next `n - 1` lines is `parent key L|R`
+    ///
+    /// Nodes are numbered in input order, the root is 0 and the node of the i-th
+    /// edge line is i, so `parent` must refer to a node of a previous line.
+    /// The resulting ids are the same that `add_node` would return.
+    pub fn from_edge_list(input: &str) -> Result<Self, ParseTreeError> {
+        // Skip blank lines, but keep the original line numbers for the errors
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let (line, n) = lines.next().ok_or(ParseTreeError::UnexpectedEnd)?;
+        let n: usize = parse_line_token(n, line)?;
+
+        let mut tree = Tree::new();
+        if n > 0 {
+            let (line, key) = lines.next().ok_or(ParseTreeError::UnexpectedEnd)?;
+            tree = Tree::with_root(parse_line_token(key, line)?);
+        }
+
+        for _ in 1..n {
+            let (line, edge) = lines.next().ok_or(ParseTreeError::UnexpectedEnd)?;
+            let tokens: Vec<&str> = edge.split_whitespace().collect();
+            let [parent, key, side] = tokens[..] else {
+                return Err(ParseTreeError::WrongTokenCount { line });
+            };
+
+            let parent: usize = parse_line_token(parent, line)?;
+            let key: T = parse_line_token(key, line)?;
+            let is_left = match side {
+                "L" => true,
+                "R" => false,
+                _ => return Err(bad_line_token(side, line)),
+            };
+
+            tree.check_free_slot(parent, is_left)
+                .map_err(|err| match err {
+                    TreeError::ChildAlreadySet { .. } => ParseTreeError::ChildAlreadySet {
+                        line,
+                        parent,
+                        is_left,
+                    },
+                    _ => ParseTreeError::MissingParent { line, parent },
+                })?;
+            tree.attach(parent, key, is_left);
+        }
+
+        match lines.next() {
+            Some((line, _)) => Err(ParseTreeError::TrailingInput { line }),
+            None => {
+                if let Some(root) = tree.root {
+                    tree.count_attached(root);
+                }
+                Ok(tree)
+            }
+        }
+    }
+
+    /// Builds a tree from the LeetCode level-order format, e.g. `[1,2,null,3]`.
+    /// Missing children are written as `null` and trailing `null`s can be omitted.
+    /// `[]` is the empty tree.
+    pub fn from_level_order(input: &str) -> Result<Self, ParseTreeError> {
+        let list = input
+            .trim()
+            .strip_prefix('[')
+            .and_then(|list| list.strip_suffix(']'))
+            .ok_or(ParseTreeError::MissingBrackets)?;
+
+        // `[]` has no tokens, otherwise every token between commas must be there
+        let tokens = if list.trim().is_empty() {
+            vec![]
+        } else {
+            list.split(',').map(str::trim).collect()
+        };
+        let mut tokens = tokens
+            .into_iter()
+            .enumerate()
+            .map(|(position, token)| match token {
+                "null" => Ok(None),
+                _ => parse_token(token, position).map(Some),
+            });
+
+        let mut tree = match tokens.next().transpose()? {
+            Some(Some(key)) => Tree::with_root(key),
+            Some(None) | None => Tree::new(),
+        };
+
+        // Nodes whose children have not been read yet, in level order
+        let mut queue: VecDeque<usize> = tree.root.into_iter().collect();
+        let mut position = 0;
+
+        'slots: while let Some(parent) = queue.pop_front() {
+            for is_left in [true, false] {
+                position += 1;
+                match tokens.next().transpose()? {
+                    Some(Some(key)) => queue.push_back(tree.attach(parent, key, is_left)),
+                    Some(None) => {}
+                    None => break 'slots,
+                }
+            }
+        }
+
+        // Either the tokens are over or all the slots are filled, only nulls can follow
+        for token in tokens {
+            position += 1;
+            if token?.is_some() {
+                return Err(ParseTreeError::OrphanNode { position });
+            }
+        }
+        if let Some(root) = tree.root {
+            tree.count_attached(root);
+        }
+        Ok(tree)
+    }
+}
+
+impl<T: fmt::Display> Tree<T> {
+    /// Returns the tree in the LeetCode level-order format, e.g. `[1,2,null,3]`,
+    /// without trailing `null`s. It is the inverse of `from_level_order`.
+    pub fn to_level_order(&self) -> String {
+        let mut tokens = Vec::new();
+        let mut queue: VecDeque<Option<usize>> = VecDeque::from([self.root]);
+
+        while let Some(node_id) = queue.pop_front() {
+            match node_id {
+                Some(id) => {
+                    let node = &self.nodes[id];
+                    tokens.push(node.key.to_string());
+                    queue.push_back(node.id_left);
+                    queue.push_back(node.id_right);
+                }
+                None => tokens.push("null".to_string()),
+            }
+        }
+
+        while tokens.last().is_some_and(|token| token == "null") {
+            tokens.pop();
+        }
+        format!("[{}]", tokens.join(","))
+    }
+}
+
+fn parse_token<V: FromStr>(token: &str, position: usize) -> Result<V, ParseTreeError> {
+    token.parse().map_err(|_| bad_token(token, position))
+}
+
+fn parse_line_token<V: FromStr>(token: &str, line: usize) -> Result<V, ParseTreeError> {
+    token.parse().map_err(|_| bad_line_token(token, line))
+}
+
+fn bad_line_token(token: &str, line: usize) -> ParseTreeError {
+    ParseTreeError::BadLineToken {
+        line,
+        token: token.to_string(),
+    }
+}
+
+fn bad_token(token: &str, position: usize) -> ParseTreeError {
+    ParseTreeError::BadToken {
+        position,
+        token: token.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_edge_list() {
+        let input = "7
+            40
+            0 30 L
+            0 50 R
+            1 25 L
+            1 35 R
+            2 45 L
+            2 60 R";
+        let tree: Tree<u32> = Tree::from_edge_list(input).unwrap();
+
+        //       40
+        //     /    \
+        //   30      50
+        //  / \     / \
+        // 25 35  45  60
+
+        assert_eq!(tree.sum(), 285);
+        assert!(tree.is_bst());
+        assert_eq!(tree.to_level_order(), "[40,30,50,25,35,45,60]");
+        assert_eq!(tree.kth_smallest(3), Some(40));
+    }
+
+    #[test]
+    fn test_from_edge_list_long_chain() {
+        let n = 1_000_000;
+        let mut input = format!("{}\n0\n", n);
+        for id in 0..n - 1 {
+            input += &format!("{} {} R\n", id, id + 1);
+        }
+        let tree: Tree<u64> = Tree::from_edge_list(&input).unwrap();
+
+        assert_eq!(tree.sum(), (n * (n - 1) / 2) as u64);
+        assert_eq!(tree.kth_smallest(n / 2), Some(n as u64 / 2));
+        assert_eq!(tree.rank(&(n as u64)), n);
+    }
+
+    #[test]
+    fn test_from_edge_list_empty() {
+        let tree: Tree<i32> = Tree::from_edge_list("0\n").unwrap();
+        assert_eq!(tree.to_level_order(), "[]");
+        assert_eq!(tree.max_path_sum(), None);
+    }
+
+    #[test]
+    fn test_from_edge_list_errors() {
+        assert_eq!(
+            Tree::<i32>::from_edge_list("").err(),
+            Some(ParseTreeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Tree::<i32>::from_edge_list("3\n1\n0 2 L").err(),
+            Some(ParseTreeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Tree::<i32>::from_edge_list("2\n1\n0 2 L\n0 3 R").err(),
+            Some(ParseTreeError::TrailingInput { line: 4 })
+        );
+        assert_eq!(
+            Tree::<i32>::from_edge_list("2\n1\n0 2").err(),
+            Some(ParseTreeError::WrongTokenCount { line: 3 })
+        );
+        assert_eq!(
+            Tree::<i32>::from_edge_list("2\n1\n0 2 X").err(),
+            Some(ParseTreeError::BadLineToken {
+                line: 3,
+                token: "X".to_string()
+            })
+        );
+        assert_eq!(
+            Tree::<u32>::from_edge_list("2\n1\n0 -2 L").err(),
+            Some(ParseTreeError::BadLineToken {
+                line: 3,
+                token: "-2".to_string()
+            })
+        );
+        assert_eq!(
+            Tree::<i32>::from_edge_list("3\n1\n2 2 L\n0 3 R").err(),
+            Some(ParseTreeError::MissingParent { line: 3, parent: 2 })
+        );
+        assert_eq!(
+            Tree::<i32>::from_edge_list("3\n1\n0 2 L\n0 3 L").err(),
+            Some(ParseTreeError::ChildAlreadySet {
+                line: 4,
+                parent: 0,
+                is_left: true
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_level_order() {
+        let tree: Tree<i32> = Tree::from_level_order("[10, 5, null, 3, null, null, 4]").unwrap();
+
+        //       10
+        //      /
+        //     5
+        //    /
+        //   3
+        //    \
+        //     4
+
+        assert_eq!(tree.sum(), 22);
+        assert_eq!(tree.max_path_sum(), Some(22));
+        assert!(tree.is_bst());
+        assert_eq!(tree.to_level_order(), "[10,5,null,3,null,null,4]");
+    }
+
+    #[test]
+    fn test_level_order_round_trip() {
+        for input in [
+            "[]",
+            "[1]",
+            "[1,2,3]",
+            "[1,null,2,null,3]",
+            "[1,2,null,3]",
+            "[-1,2,3,null,4,5,null,null,-6]",
+        ] {
+            let tree: Tree<i32> = Tree::from_level_order(input).unwrap();
+            assert_eq!(tree.to_level_order(), input);
+        }
+
+        // Trailing nulls are dropped
+        let tree: Tree<i32> = Tree::from_level_order("[1,2,null,null,null]").unwrap();
+        assert_eq!(tree.to_level_order(), "[1,2]");
+        let tree: Tree<i32> = Tree::from_level_order("[null]").unwrap();
+        assert_eq!(tree.to_level_order(), "[]");
+    }
+
+    #[test]
+    fn test_from_level_order_errors() {
+        assert_eq!(
+            Tree::<i32>::from_level_order("1,2,3").err(),
+            Some(ParseTreeError::MissingBrackets)
+        );
+        // Empty tokens are reported, not skipped
+        assert_eq!(
+            Tree::<i32>::from_level_order("[1,,2]").err(),
+            Some(ParseTreeError::BadToken {
+                position: 1,
+                token: String::new()
+            })
+        );
+        assert_eq!(
+            Tree::<i32>::from_level_order("[1,2,]").err(),
+            Some(ParseTreeError::BadToken {
+                position: 2,
+                token: String::new()
+            })
+        );
+        assert_eq!(
+            Tree::<i32>::from_level_order("[1,two,3]").err(),
+            Some(ParseTreeError::BadToken {
+                position: 1,
+                token: "two".to_string()
+            })
+        );
+        assert_eq!(
+            Tree::<i32>::from_level_order("[1,null,null,4]").err(),
+            Some(ParseTreeError::OrphanNode { position: 3 })
+        );
+        assert_eq!(
+            Tree::<i32>::from_level_order("[null,1]").err(),
+            Some(ParseTreeError::OrphanNode { position: 1 })
+        );
+    }
+}
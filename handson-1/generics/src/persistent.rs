@@ -0,0 +1,452 @@
+use std::ops::Add;
+
+use crate::{Tree, TreeError};
+
+/// Handle to one version of a `PersistentTree`, returned by every edit.
+/// It is valid only for the tree that returned it, and it stays valid forever:
+/// the versions are never changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Version {
+    // None for the empty tree
+    root: Option<usize>,
+}
+
+impl Version {
+    /// The version with no nodes, valid for every `PersistentTree`
+    pub const EMPTY: Version = Version { root: None };
+
+    /// Returns if the version has no nodes
+    pub fn is_empty(self) -> bool {
+        self.root.is_none()
+    }
+}
+
+struct PersistentNode<T> {
+    key: T,
+    id_left: Option<usize>,
+    id_right: Option<usize>,
+}
+
+/// Binary tree that keeps all its versions in a shared arena.
+/// The nodes are never changed: an edit copies the nodes on the path from the root
+/// to the edited node and shares all the other ones with the previous version,
+/// so it takes O(depth) time and space.
+/// The nodes are addressed by their path from the root, `true` for a step into
+/// the left child and `false` for a step into the right child, the empty path
+/// is the root.
+pub struct PersistentTree<T> {
+    nodes: Vec<PersistentNode<T>>,
+}
+
+impl<T: Ord + Add<Output = T> + Default + Copy> Default for PersistentTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Add<Output = T> + Default + Copy> PersistentTree<T> {
+    pub fn new() -> Self {
+        Self { nodes: Vec::new() }
+    }
+
+    /// Returns the number of nodes stored for all the versions together
+    pub fn arena_len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns a version with a single node with `key`
+    pub fn with_root(&mut self, key: T) -> Version {
+        Version {
+            root: Some(self.alloc(key, None, None)),
+        }
+    }
+
+    /// Copies the nodes of `tree` and returns them as a version. It takes O(n).
+    pub fn from_tree(&mut self, tree: &Tree<T>) -> Version {
+        // The nodes are copied in post-order, so both children are already copied
+        let mut copies = vec![None; tree.nodes.len()];
+        for (id, &key) in tree.iter_postorder() {
+            let node = &tree.nodes[id];
+            let (left, right) = (node.id_left, node.id_right);
+            copies[id] = Some(self.alloc(
+                key,
+                left.and_then(|left| copies[left]),
+                right.and_then(|right| copies[right]),
+            ));
+        }
+        Version {
+            root: tree.root.and_then(|root| copies[root]),
+        }
+    }
+
+    /// Returns a `Tree` with the nodes of `version`, numbered in pre-order. It takes O(n).
+    pub fn to_tree(&self, version: Version) -> Tree<T> {
+        let Some(root) = version.root else {
+            return Tree::new();
+        };
+        let mut tree = Tree::with_root(self.nodes[root].key);
+        // Nodes to copy, as (node, parent in the new tree, is_left)
+        let mut stack = vec![];
+        self.push_children(&mut stack, root, 0);
+        while let Some((id, parent, is_left)) = stack.pop() {
+            let new_id = tree.attach(parent, self.nodes[id].key, is_left);
+            self.push_children(&mut stack, id, new_id);
+        }
+        tree.count_attached(0);
+        tree
+    }
+
+    /// Adds a child to the node at `path` in `version`, and returns the new version.
+    /// The new node is the left child iff `is_left` is true.
+    ///
+    /// # Panics
+    /// Panics if there is no node at `path`, or if the node has the child already set.
+    /// `try_add_node` returns the error instead.
+    pub fn add_node(&mut self, version: Version, path: &[bool], key: T, is_left: bool) -> Version {
+        self.try_add_node(version, path, key, is_left)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Adds a child like `add_node`, or returns an error if there is no node at `path`
+    /// or if the node has the child already set.
+    pub fn try_add_node(
+        &mut self,
+        version: Version,
+        path: &[bool],
+        key: T,
+        is_left: bool,
+    ) -> Result<Version, TreeError> {
+        let ids = self.find(version, path)?;
+        let parent_id = *ids.last().unwrap();
+        let parent = &self.nodes[parent_id];
+        let child = if is_left {
+            parent.id_left
+        } else {
+            parent.id_right
+        };
+        if child.is_some() {
+            return Err(TreeError::ChildAlreadySet { parent_id, is_left });
+        }
+
+        let (parent_key, left, right) = (parent.key, parent.id_left, parent.id_right);
+        let new_id = self.alloc(key, None, None);
+        let copy = if is_left {
+            self.alloc(parent_key, Some(new_id), right)
+        } else {
+            self.alloc(parent_key, left, Some(new_id))
+        };
+        Ok(self.copy_path(&ids, path, copy))
+    }
+
+    /// Replaces the key of the node at `path` in `version`, and returns the new version.
+    ///
+    /// # Panics
+    /// Panics if there is no node at `path`. `try_set_key` returns the error instead.
+    pub fn set_key(&mut self, version: Version, path: &[bool], key: T) -> Version {
+        self.try_set_key(version, path, key)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Replaces the key like `set_key`, or returns an error if there is no node at `path`
+    pub fn try_set_key(
+        &mut self,
+        version: Version,
+        path: &[bool],
+        key: T,
+    ) -> Result<Version, TreeError> {
+        let ids = self.find(version, path)?;
+        let node = &self.nodes[*ids.last().unwrap()];
+        let copy = self.alloc(key, node.id_left, node.id_right);
+        Ok(self.copy_path(&ids, path, copy))
+    }
+
+    /// Returns the key of the node at `path` in `version`, None if there is no such node
+    pub fn key(&self, version: Version, path: &[bool]) -> Option<T> {
+        let ids = self.find(version, path).ok()?;
+        Some(self.nodes[*ids.last().unwrap()].key)
+    }
+
+    /// Returns the number of nodes in `version`
+    pub fn len(&self, version: Version) -> usize {
+        self.iter_inorder(version).count()
+    }
+
+    /// Returns the sum of all the keys in `version`
+    pub fn sum(&self, version: Version) -> T {
+        self.iter_inorder(version)
+            .fold(T::default(), |sum, key| sum + key)
+    }
+
+    /// Returns if `version` is a binary search tree, with the same check as `Tree::is_bst`
+    pub fn is_bst(&self, version: Version) -> bool {
+        let mut previous_key: Option<T> = None;
+
+        for key in self.iter_inorder(version) {
+            if previous_key.is_some_and(|previous_key| previous_key > key) {
+                return false;
+            }
+            previous_key = Some(key);
+        }
+        true
+    }
+
+    /// Returns the maximum path sum of `version` like `Tree::max_path_sum`,
+    /// None if the version is empty
+    pub fn max_path_sum(&self, version: Version) -> Option<T> {
+        // Maximum sum of a path that goes down from each visited node.
+        // The nodes are visited in post-order, so the sums of the children
+        // are on top of the stack when their parent is visited.
+        let mut down: Vec<T> = vec![];
+        let mut best: Option<T> = None;
+        // Nodes to visit, as (node, children already visited)
+        let mut stack: Vec<(usize, bool)> =
+            version.root.map(|root| (root, false)).into_iter().collect();
+
+        while let Some((id, visited)) = stack.pop() {
+            let node = &self.nodes[id];
+            if !visited {
+                stack.push((id, true));
+                stack.extend(node.id_right.map(|right| (right, false)));
+                stack.extend(node.id_left.map(|left| (left, false)));
+                continue;
+            }
+
+            // Keep a child only if its downward path increases the sum
+            let key = node.key;
+            let increases = |sum: &T| key + *sum > key;
+            let right = node.id_right.and_then(|_| down.pop()).filter(increases);
+            let left = node.id_left.and_then(|_| down.pop()).filter(increases);
+
+            down.push(left.max(right).map_or(key, |sum| key + sum));
+            // The path with the current node on top joins the two downward paths
+            let sum = match (left, right) {
+                (Some(left), Some(right)) => key + left + right,
+                (left, right) => left.or(right).map_or(key, |sum| key + sum),
+            };
+            best = best.max(Some(sum));
+        }
+        best
+    }
+
+    /// Returns the keys of `version` in in-order
+    fn iter_inorder(&self, version: Version) -> impl Iterator<Item = T> + '_ {
+        let mut stack: Vec<usize> = vec![];
+        let mut current = version.root;
+        std::iter::from_fn(move || {
+            // Go down to the leftmost node not visited yet
+            while let Some(id) = current {
+                stack.push(id);
+                current = self.nodes[id].id_left;
+            }
+            let id = stack.pop()?;
+            current = self.nodes[id].id_right;
+            Some(self.nodes[id].key)
+        })
+    }
+
+    /// Returns the ids of the nodes on `path` in `version`, from the root
+    fn find(&self, version: Version, path: &[bool]) -> Result<Vec<usize>, TreeError> {
+        let mut ids = Vec::with_capacity(path.len() + 1);
+        let mut current = version.root;
+        for (depth, &is_left) in path.iter().enumerate() {
+            let id = current.ok_or(TreeError::PathNotFound { depth })?;
+            ids.push(id);
+            let node = &self.nodes[id];
+            current = if is_left { node.id_left } else { node.id_right };
+        }
+        let id = current.ok_or(TreeError::PathNotFound { depth: path.len() })?;
+        ids.push(id);
+        Ok(ids)
+    }
+
+    /// Copies the ancestors of the last node of `ids`, whose copy is `copy`,
+    /// and returns the version with the copied root
+    fn copy_path(&mut self, ids: &[usize], path: &[bool], copy: usize) -> Version {
+        let mut child = copy;
+        // Go up from the parent of the edited node
+        for (&id, &is_left) in ids.iter().zip(path).rev() {
+            let node = &self.nodes[id];
+            child = if is_left {
+                self.alloc(node.key, Some(child), node.id_right)
+            } else {
+                self.alloc(node.key, node.id_left, Some(child))
+            };
+        }
+        Version { root: Some(child) }
+    }
+
+    fn push_children(&self, stack: &mut Vec<(usize, usize, bool)>, id: usize, new_id: usize) {
+        let node = &self.nodes[id];
+        stack.extend(node.id_right.map(|right| (right, new_id, false)));
+        stack.extend(node.id_left.map(|left| (left, new_id, true)));
+    }
+
+    fn alloc(&mut self, key: T, id_left: Option<usize>, id_right: Option<usize>) -> usize {
+        self.nodes.push(PersistentNode {
+            key,
+            id_left,
+            id_right,
+        });
+        self.nodes.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Shape, TreeGenerator};
+
+    #[test]
+    fn test_versions() {
+        let mut tree = PersistentTree::new();
+        let v0 = tree.with_root(10);
+        let v1 = tree.add_node(v0, &[], 5, true);
+        let v2 = tree.add_node(v1, &[], 15, false);
+        let v3 = tree.add_node(v2, &[true], 20, false);
+
+        //  v2:  10        v3:  10
+        //      /  \           /  \
+        //     5    15        5    15
+        //                     \
+        //                      20
+        assert_eq!(tree.to_tree(v2).to_level_order(), "[10,5,15]");
+        assert_eq!(tree.to_tree(v3).to_level_order(), "[10,5,15,null,20]");
+
+        // The old versions are not changed
+        assert_eq!(tree.len(v0), 1);
+        assert_eq!(tree.sum(v1), 15);
+        assert_eq!(tree.sum(v2), 30);
+        assert_eq!(tree.sum(v3), 50);
+        assert!(tree.is_bst(v2));
+        assert!(!tree.is_bst(v3));
+        assert_eq!(tree.key(v2, &[true, false]), None);
+        assert_eq!(tree.key(v3, &[true, false]), Some(20));
+
+        // A new branch from an old version
+        let v4 = tree.set_key(v2, &[false], 8);
+        assert_eq!(tree.to_tree(v4).to_level_order(), "[10,5,8]");
+        assert!(!tree.is_bst(v4));
+        assert!(tree.is_bst(v2));
+    }
+
+    #[test]
+    fn test_empty() {
+        let tree: PersistentTree<i32> = PersistentTree::new();
+        assert!(Version::EMPTY.is_empty());
+        assert_eq!(tree.len(Version::EMPTY), 0);
+        assert_eq!(tree.sum(Version::EMPTY), 0);
+        assert!(tree.is_bst(Version::EMPTY));
+        assert_eq!(tree.max_path_sum(Version::EMPTY), None);
+        assert!(tree.to_tree(Version::EMPTY).is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut tree = PersistentTree::new();
+        let v0 = tree.with_root(1);
+        let v1 = tree.add_node(v0, &[], 2, true);
+
+        assert_eq!(
+            tree.try_add_node(v1, &[], 3, true),
+            Err(TreeError::ChildAlreadySet {
+                parent_id: 2,
+                is_left: true
+            })
+        );
+        assert_eq!(
+            tree.try_add_node(v1, &[true, true], 3, true),
+            Err(TreeError::PathNotFound { depth: 2 })
+        );
+        assert_eq!(
+            tree.try_set_key(v1, &[false], 3),
+            Err(TreeError::PathNotFound { depth: 1 })
+        );
+        assert_eq!(
+            tree.try_set_key(Version::EMPTY, &[], 3),
+            Err(TreeError::PathNotFound { depth: 0 })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Node at the end of the path does not exist")]
+    fn test_add_node_panics() {
+        let mut tree = PersistentTree::new();
+        let v0 = tree.with_root(1);
+        tree.add_node(v0, &[false], 2, true);
+    }
+
+    #[test]
+    fn test_path_copying() {
+        let mut generator = TreeGenerator::new(12);
+        let original = generator.tree(1000, Shape::Complete, -100i64..=100);
+        let mut tree = PersistentTree::new();
+        let v0 = tree.from_tree(&original);
+        assert_eq!(tree.arena_len(), 1000);
+
+        // An edit at depth 9 copies the 10 nodes from the root
+        let v1 = tree.set_key(v0, &[true; 9], 1_000);
+        assert_eq!(tree.arena_len(), 1010);
+        assert_eq!(
+            tree.sum(v1),
+            original.sum() - tree.key(v0, &[true; 9]).unwrap() + 1_000
+        );
+        assert_eq!(tree.sum(v0), original.sum());
+    }
+
+    #[test]
+    fn test_queries_match_tree() {
+        let mut generator = TreeGenerator::new(13);
+        for n in 0..60 {
+            let shape = [Shape::RandomBinary, Shape::RandomBst, Shape::Chain][n % 3];
+            let original = generator.tree(n, shape, -20i32..=20);
+            let mut tree = PersistentTree::new();
+            let version = tree.from_tree(&original);
+
+            assert_eq!(tree.len(version), original.len());
+            assert_eq!(tree.sum(version), original.sum());
+            assert_eq!(tree.is_bst(version), original.is_bst());
+            assert_eq!(tree.max_path_sum(version), original.max_path_sum());
+            assert!(tree.to_tree(version).is_isomorphic(&original));
+        }
+    }
+
+    #[test]
+    fn test_many_versions() {
+        // Every version adds a node to the previous one, and each one keeps its sum
+        let mut generator = TreeGenerator::new(14);
+        let mut tree = PersistentTree::new();
+        let mut versions = vec![tree.with_root(0i64)];
+        let mut paths: Vec<Vec<bool>> = vec![vec![]];
+        let mut free: Vec<(usize, bool)> = vec![(0, true), (0, false)];
+
+        for key in 1..500 {
+            let (parent, is_left) = free.swap_remove(generator.below(free.len()));
+            let mut path = paths[parent].clone();
+            versions.push(tree.add_node(*versions.last().unwrap(), &path, key, is_left));
+            path.push(is_left);
+            paths.push(path);
+            free.extend([(key as usize, true), (key as usize, false)]);
+        }
+
+        for (i, &version) in versions.iter().enumerate() {
+            let i = i as i64;
+            assert_eq!(tree.sum(version), i * (i + 1) / 2);
+            assert_eq!(
+                tree.max_path_sum(version),
+                tree.to_tree(version).max_path_sum()
+            );
+        }
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let n = 200_000;
+        let mut tree = PersistentTree::new();
+        let chain = TreeGenerator::new(15).tree(n, Shape::Chain, 1u64..=1);
+        let version = tree.from_tree(&chain);
+
+        assert_eq!(tree.sum(version), n as u64);
+        assert_eq!(tree.max_path_sum(version), Some(n as u64));
+        assert_eq!(tree.to_tree(version).len(), n);
+    }
+}
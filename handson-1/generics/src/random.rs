@@ -0,0 +1,355 @@
+use std::ops::{Add, RangeInclusive};
+
+use crate::{DuplicatePolicy, Tree};
+
+/// Shape of the trees built by `TreeGenerator`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    /// Every node is a child of the previous one, on a random side
+    Chain,
+    /// Every level is full except the last one, filled from the left
+    Complete,
+    /// Every node is attached to a free child slot chosen uniformly at random
+    RandomBinary,
+    /// The keys are inserted in random order with `Tree::insert`, so `is_bst` holds.
+    /// Equal keys go in the right subtree.
+    RandomBst,
+    /// Random tree with exactly the given number of levels, a single node has height 1
+    Height(usize),
+}
+
+/// Seeded generator of random trees, for tests and benchmarks.
+/// The same seed always gives the same trees.
+/// It uses a linear congruential generator, so it is fast but not suited
+/// for anything that needs real randomness.
+pub struct TreeGenerator {
+    state: u64,
+}
+
+impl TreeGenerator {
+    pub fn new(seed: u64) -> Self {
+        TreeGenerator { state: seed }
+    }
+
+    /// Returns a random number in `[0, bound)`
+    pub fn below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0, "The bound must be positive");
+        // Three draws of 31 bits cover any `usize`
+        let draws = if bound <= 1 << 31 { 1 } else { 3 };
+        (self.draw(draws) % bound as u128) as usize
+    }
+
+    /// Returns a random key in `keys`.
+    /// The key type must convert to `i128` without loss, so all the primitive
+    /// integers work except `usize`, `isize` and `u128`.
+    pub fn key<T: Copy + Into<i128> + TryFrom<i128>>(&mut self, keys: &RangeInclusive<T>) -> T {
+        let (low, high): (i128, i128) = ((*keys.start()).into(), (*keys.end()).into());
+        assert!(low <= high, "Empty key range");
+
+        // Five draws of 31 bits cover the 128 bits of any span. The span of the
+        // whole i128 range is 2^128, it wraps to 0 and every offset is in range.
+        let random = self.draw(5);
+        let span = (high.wrapping_sub(low) as u128).wrapping_add(1);
+        let offset = if span == 0 { random } else { random % span };
+        match T::try_from(low.wrapping_add(offset as i128)) {
+            Ok(key) => key,
+            Err(_) => unreachable!("The key is in the range"),
+        }
+    }
+
+    /// Returns a tree with `n` nodes of the given `shape` and keys in `keys`.
+    /// The node ids go from 0 to n - 1. The key types are the ones of `key`.
+    ///
+    /// # Panics
+    /// Panics if `keys` is empty, or with `Shape::Height(h)` if `n` nodes can't
+    /// make a tree of height `h`, that is if `n < h` or `n > 2^h - 1`.
+    pub fn tree<T>(&mut self, n: usize, shape: Shape, keys: RangeInclusive<T>) -> Tree<T>
+    where
+        T: Ord + Add<Output = T> + Default + Copy + Into<i128> + TryFrom<i128>,
+    {
+        if let Shape::Height(height) = shape {
+            let max_nodes = 1usize
+                .checked_shl(height as u32)
+                .map_or(usize::MAX, |max| max - 1);
+            assert!(
+                height <= n && n <= max_nodes,
+                "No tree of {} nodes has height {}",
+                n,
+                height
+            );
+        }
+        if n == 0 {
+            return Tree::new();
+        }
+
+        match shape {
+            Shape::Chain => {
+                let mut tree = Tree::with_root(self.key(&keys));
+                for parent in 0..n - 1 {
+                    let is_left = self.below(2) == 0;
+                    tree.attach(parent, self.key(&keys), is_left);
+                }
+                // The sizes are counted once at the end, so they are never stale
+                tree.count_attached(0);
+                tree
+            }
+            Shape::Complete => {
+                // The children of i are 2i + 1 and 2i + 2, like in an array heap
+                let mut tree = Tree::with_root(self.key(&keys));
+                for id in 1..n {
+                    tree.attach((id - 1) / 2, self.key(&keys), id % 2 == 1);
+                }
+                tree.count_attached(0);
+                tree
+            }
+            Shape::RandomBinary => self.random_tree(n, usize::MAX, &keys, |_, _| {}),
+            Shape::RandomBst => {
+                let mut tree = Tree::new();
+                tree.set_duplicate_policy(DuplicatePolicy::Right);
+                for _ in 0..n {
+                    tree.insert(self.key(&keys));
+                }
+                tree.set_duplicate_policy(DuplicatePolicy::Reject);
+                tree
+            }
+            Shape::Height(height) => {
+                // A spine of `height` nodes, then the other nodes in the free slots
+                // that are not deeper than the spine
+                self.random_tree(n, height, &keys, |generator, tree| {
+                    let mut last = 0;
+                    for _ in 1..height {
+                        let is_left = generator.below(2) == 0;
+                        last = tree.attach(last, generator.key(&keys), is_left);
+                    }
+                })
+            }
+        }
+    }
+
+    /// Builds a tree of `n` nodes attaching every node to a random free slot
+    /// with at most `height` levels. `start` adds the first nodes after the root.
+    fn random_tree<T>(
+        &mut self,
+        n: usize,
+        height: usize,
+        keys: &RangeInclusive<T>,
+        start: impl Fn(&mut Self, &mut Tree<T>),
+    ) -> Tree<T>
+    where
+        T: Ord + Add<Output = T> + Default + Copy + Into<i128> + TryFrom<i128>,
+    {
+        let mut tree = Tree::with_root(self.key(keys));
+        start(self, &mut tree);
+
+        // Free slots as (parent, is_left), only where a child would have at most
+        // `height` levels above it. Depth of every node, the root is on level 1.
+        let mut level = vec![1; tree.nodes.len()];
+        let mut slots = vec![];
+        for (id, _) in tree.iter_preorder() {
+            let node = &tree.nodes[id];
+            for (child, is_left) in [(node.id_left, true), (node.id_right, false)] {
+                match child {
+                    Some(child) => level[child] = level[id] + 1,
+                    None if level[id] < height => slots.push((id, is_left)),
+                    None => {}
+                }
+            }
+        }
+
+        while tree.nodes.len() < n {
+            let (parent, is_left) = slots.swap_remove(self.below(slots.len()));
+            let id = tree.attach(parent, self.key(keys), is_left);
+            level.push(level[parent] + 1);
+            if level[id] < height {
+                slots.push((id, true));
+                slots.push((id, false));
+            }
+        }
+        tree.count_attached(0);
+        tree
+    }
+
+    /// Returns `draws` steps of the generator, 31 bits each, packed in one number
+    fn draw(&mut self, draws: usize) -> u128 {
+        (0..draws).fold(0, |random, _| {
+            self.state = self
+                .state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            // The high bits of an LCG are the most random ones
+            (random << 31) | (self.state >> 33) as u128
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LcaIndex;
+
+    /// Returns the number of levels of the tree
+    fn height<T>(tree: &Tree<T>) -> usize {
+        let index = LcaIndex::new(tree);
+        tree.iter_preorder()
+            .map(|(id, _)| index.depth(id) + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_sizes_and_keys() {
+        let mut generator = TreeGenerator::new(1);
+        let shapes = [
+            Shape::Chain,
+            Shape::Complete,
+            Shape::RandomBinary,
+            Shape::RandomBst,
+            Shape::Height(12),
+        ];
+
+        for shape in shapes {
+            let tree = generator.tree(500, shape, -10i32..=10);
+            assert_eq!(tree.len(), 500);
+            assert!(tree
+                .iter_preorder()
+                .all(|(_, &key)| (-10..=10).contains(&key)));
+        }
+        assert!(generator.tree(0, Shape::RandomBinary, 0u8..=1).is_empty());
+    }
+
+    #[test]
+    fn test_shapes() {
+        let mut generator = TreeGenerator::new(2);
+
+        assert_eq!(height(&generator.tree(100, Shape::Chain, 0u32..=9)), 100);
+        assert_eq!(height(&generator.tree(100, Shape::Complete, 0u32..=9)), 7);
+        assert_eq!(height(&generator.tree(127, Shape::Complete, 0u32..=9)), 7);
+        assert_eq!(height(&generator.tree(128, Shape::Complete, 0u32..=9)), 8);
+
+        for h in 1..10 {
+            for n in [h, (h + (1 << h) - 1) / 2, (1 << h) - 1] {
+                assert_eq!(height(&generator.tree(n, Shape::Height(h), 0u32..=9)), h);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "No tree of 8 nodes has height 3")]
+    fn test_impossible_height() {
+        TreeGenerator::new(3).tree(8, Shape::Height(3), 0u32..=9);
+    }
+
+    #[test]
+    fn test_random_bst() {
+        let mut generator = TreeGenerator::new(4);
+        for n in 0..50 {
+            let tree = generator.tree(n, Shape::RandomBst, 0i64..=20);
+            assert!(tree.is_bst());
+            assert_eq!(tree.len(), n);
+        }
+    }
+
+    #[test]
+    fn test_seed() {
+        let first = TreeGenerator::new(42).tree(200, Shape::RandomBinary, i64::MIN..=i64::MAX);
+        let again = TreeGenerator::new(42).tree(200, Shape::RandomBinary, i64::MIN..=i64::MAX);
+        let other = TreeGenerator::new(43).tree(200, Shape::RandomBinary, i64::MIN..=i64::MAX);
+
+        assert_eq!(first.to_level_order(), again.to_level_order());
+        assert_ne!(first.to_level_order(), other.to_level_order());
+        // The keys are spread across the whole range, not only its lowest part
+        assert!(first.iter_preorder().any(|(_, &key)| key > 0));
+        assert!(first.iter_preorder().any(|(_, &key)| key < i64::MIN / 2));
+    }
+
+    #[test]
+    fn test_key_spread() {
+        // Every quarter of the range gets about a quarter of the keys
+        let mut generator = TreeGenerator::new(1);
+        let mut quarters = [0; 4];
+        for _ in 0..10_000 {
+            let key = generator.key(&(i64::MIN..=i64::MAX));
+            quarters[((key as i128 - i64::MIN as i128) >> 62) as usize] += 1;
+        }
+        assert!(quarters
+            .iter()
+            .all(|&count| (2_000..3_000).contains(&count)));
+
+        let mut quarters = [0; 4];
+        for _ in 0..10_000 {
+            let key = generator.key(&(i128::MIN..=i128::MAX));
+            quarters[((key as u128) >> 126) as usize] += 1;
+        }
+        assert!(quarters
+            .iter()
+            .all(|&count| (2_000..3_000).contains(&count)));
+
+        // Small ranges include both ends
+        let keys: Vec<u8> = (0..100).map(|_| generator.key(&(3u8..=5))).collect();
+        assert!((3..=5).all(|key| keys.contains(&key)));
+    }
+
+    #[test]
+    fn test_below_spread() {
+        // A bound above 2^31 needs more than one draw of 31 bits
+        let mut generator = TreeGenerator::new(2);
+        let bound = 1usize << 40;
+        let mut quarters = [0; 4];
+        for _ in 0..10_000 {
+            let value = generator.below(bound);
+            assert!(value < bound);
+            quarters[value >> 38] += 1;
+        }
+        assert!(quarters
+            .iter()
+            .all(|&count| (2_000..3_000).contains(&count)));
+
+        assert!((0..100).all(|_| generator.below(usize::MAX) < usize::MAX));
+        let values: Vec<usize> = (0..100).map(|_| generator.below(3)).collect();
+        assert!((0..3).all(|value| values.contains(&value)));
+    }
+
+    /// Maximum path sum trying every pair of end nodes
+    fn brute_force_max_path_sum(tree: &Tree<i32>) -> Option<i32> {
+        let index = LcaIndex::new(tree);
+        let ids: Vec<usize> = tree.iter_preorder().map(|(id, _)| id).collect();
+        let up_sum = |mut id: usize, top: usize| {
+            let mut sum = 0;
+            while id != top {
+                sum += tree.nodes[id].key;
+                id = index.kth_ancestor(id, 1).unwrap();
+            }
+            sum
+        };
+
+        let mut best = None;
+        for &u in &ids {
+            for &v in &ids {
+                let top = index.lca(u, v);
+                let sum = up_sum(u, top) + up_sum(v, top) + tree.nodes[top].key;
+                best = best.max(Some(sum));
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn test_max_path_sum_oracle() {
+        let mut generator = TreeGenerator::new(5);
+        for n in 1..40 {
+            let shape = [Shape::RandomBinary, Shape::Chain, Shape::Complete][n % 3];
+            let tree = generator.tree(n, shape, -20i32..=20);
+            assert_eq!(tree.max_path_sum(), brute_force_max_path_sum(&tree));
+        }
+    }
+
+    #[test]
+    fn test_large_trees() {
+        let mut generator = TreeGenerator::new(6);
+        let tree = generator.tree(1_000_000, Shape::RandomBinary, 1u32..=1);
+        assert_eq!(tree.sum(), 1_000_000);
+
+        let tree = generator.tree(1_000_000, Shape::Height(100), 0u64..=0);
+        assert_eq!(height(&tree), 100);
+    }
+}
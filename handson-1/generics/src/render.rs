@@ -0,0 +1,138 @@
+use std::fmt::{self, Write};
+
+use crate::Tree;
+
+impl<T: fmt::Display> Tree<T> {
+    /// Returns the tree as a Graphviz digraph. Every node is labelled with its key
+    /// and every edge with `L` or `R`.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(|_, _| None)
+    }
+
+    /// Returns the tree as a Graphviz digraph, like `to_dot`.
+    /// `annotate` is called on every `(node_id, &key)` and, if it returns some text,
+    /// the text is shown in the node under the key.
+    pub fn to_dot_with(&self, annotate: impl Fn(usize, &T) -> Option<String>) -> String {
+        let mut dot = String::from("digraph Tree {\n");
+
+        for (id, key) in self.iter_preorder() {
+            let label = match annotate(id, key) {
+                Some(annotation) => format!("{}\\n{}", key, annotation),
+                None => key.to_string(),
+            };
+            // writeln! on a String can't fail
+            writeln!(dot, "    n{} [label=\"{}\"];", id, escape(&label)).unwrap();
+
+            let node = &self.nodes[id];
+            if let Some(left) = node.id_left {
+                writeln!(dot, "    n{} -> n{} [label=\"L\"];", id, left).unwrap();
+            }
+            if let Some(right) = node.id_right {
+                writeln!(dot, "    n{} -> n{} [label=\"R\"];", id, right).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Escapes the double quotes, so that the label doesn't close the DOT string
+fn escape(label: &str) -> String {
+    label.replace('"', "\\\"")
+}
+
+/// Draws the tree sideways: the root is on the left, the right subtree above it
+/// and the left subtree below it. Every line is `node_id: key`, indented by the depth.
+impl<T: fmt::Display> fmt::Display for Tree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Reverse in-order visit (right, node, left) with an explicit stack.
+        // Triples of (node_id, level, right subtree already pushed)
+        let mut stack: Vec<(usize, usize, bool)> =
+            self.root.map(|id| (id, 0, false)).into_iter().collect();
+
+        while let Some((id, level, expanded)) = stack.pop() {
+            let node = &self.nodes[id];
+
+            if expanded {
+                writeln!(f, "{}{}: {}", "    ".repeat(level), id, node.key)?;
+                continue;
+            }
+
+            // Print left subtree after the node, the right one before
+            stack.extend(node.id_left.map(|left| (left, level + 1, false)));
+            stack.push((id, level, true));
+            stack.extend(node.id_right.map(|right| (right, level + 1, false)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(0, 22, false); // id 2
+        tree.add_node(1, 7, false); // id 3
+
+        //     10
+        //    /  \
+        //   5    22
+        //    \
+        //     7
+        tree
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let tree = small_tree();
+        assert_eq!(
+            tree.to_dot(),
+            "digraph Tree {
+    n0 [label=\"10\"];
+    n0 -> n1 [label=\"L\"];
+    n0 -> n2 [label=\"R\"];
+    n1 [label=\"5\"];
+    n1 -> n3 [label=\"R\"];
+    n3 [label=\"7\"];
+    n2 [label=\"22\"];
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_to_dot_with_annotations() {
+        let tree = small_tree();
+        let dot = tree.to_dot_with(|id, &key| (key > 6).then(|| format!("id \"{}\"", id)));
+
+        assert!(dot.contains("n0 [label=\"10\\nid \\\"0\\\"\"];"));
+        assert!(dot.contains("n1 [label=\"5\"];"));
+        assert!(dot.contains("n3 [label=\"7\\nid \\\"3\\\"\"];"));
+    }
+
+    #[test]
+    fn test_display() {
+        let tree = small_tree();
+        assert_eq!(
+            tree.to_string(),
+            "    2: 22
+0: 10
+        3: 7
+    1: 5
+"
+        );
+    }
+
+    #[test]
+    fn test_display_empty_tree() {
+        let mut tree = small_tree();
+        tree.remove_subtree(0);
+
+        assert_eq!(tree.to_string(), "");
+        assert_eq!(tree.to_dot(), "digraph Tree {\n}\n");
+    }
+}
@@ -0,0 +1,253 @@
+use std::ops::Add;
+
+use crate::Tree;
+
+/// Diagnostics and repairs for trees that are not, or not entirely, binary search trees.
+/// The BST property is the one checked by `is_bst`: the in-order keys never decrease.
+impl<T: Ord + Add<Output = T> + Default + Copy> Tree<T> {
+    /// Returns the root id and the number of nodes of the largest subtree that is a BST,
+    /// the first in post-order if there are more with the same size.
+    /// It returns None if the tree is empty. It runs in O(n).
+    pub fn largest_bst_subtree(&self) -> Option<(usize, usize)> {
+        // For every node that roots a BST: (size, smallest key, largest key)
+        let mut bst: Vec<Option<(usize, T, T)>> = vec![None; self.nodes.len()];
+        let mut largest: Option<(usize, usize)> = None;
+
+        for (current_id, &key) in self.iter_postorder() {
+            let node = &self.nodes[current_id];
+
+            // A missing child is an empty BST, Some(None).
+            // A child that is not a BST is None.
+            let child = |id: Option<usize>| match id {
+                None => Some(None),
+                Some(id) => bst[id].map(Some),
+            };
+            let (Some(left), Some(right)) = (child(node.id_left), child(node.id_right)) else {
+                continue;
+            };
+            if left.is_some_and(|(_, _, max)| max > key)
+                || right.is_some_and(|(_, min, _)| min < key)
+            {
+                continue;
+            }
+
+            let size = 1 + left.map_or(0, |(size, ..)| size) + right.map_or(0, |(size, ..)| size);
+            let min = left.map_or(key, |(_, min, _)| min);
+            let max = right.map_or(key, |(.., max)| max);
+            bst[current_id] = Some((size, min, max));
+
+            if largest.is_none_or(|(_, largest_size)| size > largest_size) {
+                largest = Some((current_id, size));
+            }
+        }
+        largest
+    }
+
+    /// Returns the ids of the nodes whose key is smaller than the key of the node
+    /// before them in in-order, in in-order. The list is empty iff `is_bst` holds.
+    pub fn bst_violations(&self) -> Vec<usize> {
+        self.inorder_drops().into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Fixes a BST where the keys of two nodes were swapped, swapping them back.
+    /// It returns the ids of the two nodes, or None if the tree is already a BST
+    /// or it can't be fixed with a single swap; in that case the tree is not changed.
+    /// It runs in O(n).
+    pub fn recover_swapped_bst(&mut self) -> Option<(usize, usize)> {
+        let drops = self.inorder_drops();
+        let &(before_first_drop, _) = drops.first()?;
+        if drops.len() > 2 {
+            return None;
+        }
+
+        // The swap moved a key too large to the left and a key too small to the right.
+        // With repeated keys the nodes around the drops may not be the swapped ones:
+        // the second node is the last one smaller than the key before the first drop,
+        // the first node is the first one larger than the key of the second node.
+        let inorder: Vec<(usize, T)> = self.iter_inorder().map(|(id, &key)| (id, key)).collect();
+        let large = self.nodes[before_first_drop].key;
+        let &(second, small) = inorder.iter().rev().find(|&&(_, key)| key < large)?;
+        let &(first, _) = inorder.iter().find(|&&(_, key)| key > small)?;
+
+        self.swap_keys(first, second);
+        if self.is_bst() {
+            Some((first, second))
+        } else {
+            self.swap_keys(first, second);
+            None
+        }
+    }
+
+    /// Returns the pairs of consecutive in-order nodes `(previous, node)` where
+    /// the key of `node` is smaller than the key of `previous`
+    fn inorder_drops(&self) -> Vec<(usize, usize)> {
+        let mut drops = vec![];
+        let mut previous: Option<(usize, T)> = None;
+
+        for (id, &key) in self.iter_inorder() {
+            if let Some((previous_id, previous_key)) = previous {
+                if previous_key > key {
+                    drops.push((previous_id, id));
+                }
+            }
+            previous = Some((id, key));
+        }
+        drops
+    }
+
+    fn swap_keys(&mut self, a: usize, b: usize) {
+        let key = self.nodes[a].key;
+        self.nodes[a].key = self.nodes[b].key;
+        self.nodes[b].key = key;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Shape, TreeGenerator};
+
+    fn tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(0, 15, false); // id 2
+        tree.add_node(1, 1, true); // id 3
+        tree.add_node(1, 8, false); // id 4
+        tree.add_node(2, 7, false); // id 5
+        tree.add_node(4, 6, true); // id 6
+        tree.add_node(4, 9, false); // id 7
+
+        //         10
+        //       /    \
+        //      5      15
+        //     / \       \
+        //    1   8       7
+        //       / \
+        //      6   9
+        tree
+    }
+
+    #[test]
+    fn test_largest_bst_subtree() {
+        assert_eq!(tree().largest_bst_subtree(), Some((1, 5)));
+        assert_eq!(Tree::<i32>::new().largest_bst_subtree(), None);
+
+        // The whole tree is a BST
+        let mut tree = tree();
+        tree.nodes[5].key = 20;
+        assert_eq!(tree.largest_bst_subtree(), Some((0, 8)));
+
+        // Only leaves are BSTs, the first in post-order wins
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 2, true);
+        tree.add_node(0, 0, false);
+        assert_eq!(tree.largest_bst_subtree(), Some((1, 1)));
+    }
+
+    #[test]
+    fn test_largest_bst_subtree_equal_keys() {
+        // Equal keys on both sides are allowed, like in `is_bst`
+        let mut tree = Tree::with_root(5);
+        tree.add_node(0, 5, true);
+        tree.add_node(0, 5, false);
+        assert!(tree.is_bst());
+        assert_eq!(tree.largest_bst_subtree(), Some((0, 3)));
+    }
+
+    #[test]
+    fn test_bst_violations() {
+        // In-order keys: 1 5 6 8 9 10 15 7
+        assert_eq!(tree().bst_violations(), vec![5]);
+
+        let mut generator = TreeGenerator::new(8);
+        let tree = generator.tree(100, Shape::RandomBst, 0i32..=1000);
+        assert!(tree.bst_violations().is_empty());
+    }
+
+    #[test]
+    fn test_recover_swapped_bst() {
+        // In-order keys: 1 5 6 9 8 10 15 20, two adjacent nodes swapped
+        let mut tree = tree();
+        tree.nodes[5].key = 20;
+        tree.swap_keys(4, 7);
+        assert_eq!(tree.bst_violations(), vec![7]);
+
+        assert_eq!(tree.recover_swapped_bst(), Some((4, 7)));
+        assert!(tree.is_bst());
+        assert_eq!(tree.recover_swapped_bst(), None);
+    }
+
+    #[test]
+    fn test_recover_swapped_bst_far_apart() {
+        let mut tree = tree();
+        tree.nodes[5].key = 20;
+        // In-order keys: 1 5 6 8 9 10 15 20, swap 1 and 15
+        tree.swap_keys(3, 2);
+        assert_eq!(tree.bst_violations(), vec![1, 2]);
+
+        assert_eq!(tree.recover_swapped_bst(), Some((3, 2)));
+        assert!(tree.is_bst());
+        assert_eq!(tree.nodes[3].key, 1);
+    }
+
+    #[test]
+    fn test_recover_duplicate_keys() {
+        // A right chain with in-order keys 1 2 2 2 1: the drop is between the
+        // last two nodes, but the 2 to swap with the last 1 is the first one
+        let mut tree = Tree::with_root(1);
+        let mut last = 0;
+        for key in [2, 2, 2, 1] {
+            last = tree.add_node(last, key, false);
+        }
+        assert_eq!(tree.bst_violations(), vec![4]);
+
+        assert_eq!(tree.recover_swapped_bst(), Some((1, 4)));
+        assert_eq!(tree.inorder_keys(), vec![1, 1, 2, 2, 2]);
+
+        // In-order keys 3 1 1 from 1 1 3, the drop is before the first 1
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 3, true);
+        tree.add_node(0, 1, false);
+        assert_eq!(tree.recover_swapped_bst(), Some((1, 2)));
+        assert_eq!(tree.inorder_keys(), vec![1, 1, 3]);
+    }
+
+    #[test]
+    fn test_recover_not_a_single_swap() {
+        // In-order keys: 8 5 6 9 1 10 15 20, three keys out of place
+        // can't be fixed with one swap
+        let mut tree = tree();
+        tree.nodes[5].key = 20;
+        tree.nodes[3].key = 8;
+        tree.nodes[4].key = 9;
+        tree.nodes[7].key = 1;
+        let keys: Vec<i32> = tree.iter_inorder().map(|(_, &key)| key).collect();
+
+        assert_eq!(tree.recover_swapped_bst(), None);
+        let unchanged: Vec<i32> = tree.iter_inorder().map(|(_, &key)| key).collect();
+        assert_eq!(unchanged, keys);
+    }
+
+    #[test]
+    fn test_recover_random_swaps() {
+        let mut generator = TreeGenerator::new(9);
+        for n in 2..200 {
+            // Few distinct keys for the larger trees, so that they repeat
+            let keys = if n < 80 { -1000i32..=1000 } else { 0..=5 };
+            let mut tree = generator.tree(n, Shape::RandomBst, keys);
+            let keys: Vec<i32> = tree.iter_inorder().map(|(_, &key)| key).collect();
+
+            let (a, b) = (generator.below(n), generator.below(n));
+            tree.swap_keys(a, b);
+            if tree.nodes[a].key == tree.nodes[b].key {
+                // Swapping equal keys changes nothing
+                assert_eq!(tree.recover_swapped_bst(), None);
+                continue;
+            }
+
+            assert!(tree.recover_swapped_bst().is_some());
+            let recovered: Vec<i32> = tree.iter_inorder().map(|(_, &key)| key).collect();
+            assert_eq!(recovered, keys);
+        }
+    }
+}
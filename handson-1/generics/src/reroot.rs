@@ -0,0 +1,258 @@
+use crate::Tree;
+
+/// Rerooting dynamic programming on the undirected view of a tree, where every
+/// node is adjacent to its children and to its parent.
+/// The value of a node rooted at `v` is computed from its neighbours as
+/// `add_vertex(merge(add_edge(value of each neighbour subtree)), v)`.
+/// `merge` must be associative and commutative with `identity` as neutral element,
+/// like `Monoid::combine`, because the neighbours are merged in any order.
+pub trait Rerooting<T> {
+    type Value: Clone;
+
+    /// Neutral element of `merge`, the merged value of a node without neighbours
+    fn identity(&self) -> Self::Value;
+    /// Merges the contributions of two neighbours
+    fn merge(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+    /// Turns the value of a neighbour subtree into its contribution, going through the edge
+    fn add_edge(&self, value: &Self::Value) -> Self::Value;
+    /// Computes the value of the subtree rooted at `node_id` from the merged contributions
+    fn add_vertex(&self, merged: &Self::Value, node_id: usize, key: &T) -> Self::Value;
+}
+
+/// Sum of the distances from a node to all the other nodes.
+/// The value is the pair (number of nodes, sum of the distances from the root).
+pub struct SumOfDistances;
+
+impl<T> Rerooting<T> for SumOfDistances {
+    type Value = (usize, usize);
+
+    fn identity(&self) -> (usize, usize) {
+        (0, 0)
+    }
+
+    fn merge(&self, a: &(usize, usize), b: &(usize, usize)) -> (usize, usize) {
+        (a.0 + b.0, a.1 + b.1)
+    }
+
+    fn add_edge(&self, &(count, sum): &(usize, usize)) -> (usize, usize) {
+        // Every node of the subtree is one edge farther
+        (count, sum + count)
+    }
+
+    fn add_vertex(&self, &(count, sum): &(usize, usize), _: usize, _: &T) -> (usize, usize) {
+        (count + 1, sum)
+    }
+}
+
+/// Eccentricity: the distance from a node to the farthest node
+pub struct Eccentricity;
+
+impl<T> Rerooting<T> for Eccentricity {
+    type Value = usize;
+
+    fn identity(&self) -> usize {
+        0
+    }
+
+    fn merge(&self, a: &usize, b: &usize) -> usize {
+        *a.max(b)
+    }
+
+    fn add_edge(&self, height: &usize) -> usize {
+        height + 1
+    }
+
+    fn add_vertex(&self, merged: &usize, _: usize, _: &T) -> usize {
+        *merged
+    }
+}
+
+impl<T> Tree<T> {
+    /// Computes the value of `rerooting` for every node taken as root, in O(n).
+    /// A first post-order pass computes the value of every subtree toward the leaves,
+    /// a second pre-order pass computes the value of the part of the tree above every
+    /// node, then the two are merged.
+    /// The result is indexed by node id, the ids that are not in the tree get the identity.
+    pub fn reroot<R: Rerooting<T>>(&self, rerooting: &R) -> Vec<R::Value> {
+        let n = self.nodes.len();
+        let order: Vec<usize> = self.iter_preorder().map(|(id, _)| id).collect();
+
+        // Value of the subtree rooted at each node, with the original root
+        let mut down = vec![rerooting.identity(); n];
+        for &id in order.iter().rev() {
+            let merged = self
+                .children(id)
+                .fold(rerooting.identity(), |merged, child| {
+                    rerooting.merge(&merged, &rerooting.add_edge(&down[child]))
+                });
+            down[id] = rerooting.add_vertex(&merged, id, &self.nodes[id].key);
+        }
+
+        // Contribution of the parent to each node: the value of the tree without
+        // the subtree of the node, seen through the edge to the parent.
+        // The contribution is the identity for the root.
+        let mut up = vec![rerooting.identity(); n];
+        let mut answers = vec![rerooting.identity(); n];
+        for &id in order.iter() {
+            let key = &self.nodes[id].key;
+            let contributions: Vec<(usize, R::Value)> = self
+                .children(id)
+                .map(|child| (child, rerooting.add_edge(&down[child])))
+                .collect();
+
+            let all = contributions
+                .iter()
+                .fold(up[id].clone(), |merged, (_, value)| {
+                    rerooting.merge(&merged, value)
+                });
+            answers[id] = rerooting.add_vertex(&all, id, key);
+
+            // A node has at most two children, so the merge without one child is
+            // the merge of the parent contribution and the other child
+            for (i, &(child, _)) in contributions.iter().enumerate() {
+                let without_child = contributions
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .fold(up[id].clone(), |merged, (_, (_, value))| {
+                        rerooting.merge(&merged, value)
+                    });
+                up[child] = rerooting.add_edge(&rerooting.add_vertex(&without_child, id, key));
+            }
+        }
+        answers
+    }
+
+    /// Returns the sum of the distances from every node to all the other nodes
+    pub fn sum_of_distances(&self) -> Vec<usize> {
+        self.reroot(&SumOfDistances)
+            .into_iter()
+            .map(|(_, sum)| sum)
+            .collect()
+    }
+
+    /// Returns the distance from every node to its farthest node
+    pub fn eccentricities(&self) -> Vec<usize> {
+        self.reroot(&Eccentricity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LcaIndex, Shape, TreeGenerator};
+
+    fn tree() -> Tree<u32> {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 1, true); // id 1
+        tree.add_node(0, 1, false); // id 2
+        tree.add_node(1, 1, true); // id 3
+        tree.add_node(1, 1, false); // id 4
+        tree.add_node(4, 1, true); // id 5
+
+        //        0
+        //      /  \
+        //     1    2
+        //    / \
+        //   3   4
+        //      /
+        //     5
+        tree
+    }
+
+    /// Computes the answers with the distances of every pair of nodes
+    fn brute_force(tree: &Tree<u32>) -> (Vec<usize>, Vec<usize>) {
+        let index = LcaIndex::new(tree);
+        let ids: Vec<usize> = tree.iter_preorder().map(|(id, _)| id).collect();
+        let mut sums = vec![0; tree.nodes.len()];
+        let mut eccentricities = vec![0; tree.nodes.len()];
+
+        for &u in &ids {
+            let distances = ids.iter().map(|&v| index.distance(u, v));
+            sums[u] = distances.clone().sum();
+            eccentricities[u] = distances.max().unwrap();
+        }
+        (sums, eccentricities)
+    }
+
+    #[test]
+    fn test_sum_of_distances() {
+        let tree = tree();
+        assert_eq!(tree.sum_of_distances(), vec![9, 7, 13, 11, 9, 13]);
+        assert_eq!(tree.sum_of_distances(), brute_force(&tree).0);
+    }
+
+    #[test]
+    fn test_eccentricities() {
+        let tree = tree();
+        assert_eq!(tree.eccentricities(), vec![3, 2, 4, 3, 3, 4]);
+        assert_eq!(tree.eccentricities(), brute_force(&tree).1);
+    }
+
+    #[test]
+    fn test_single_node() {
+        let tree = Tree::with_root(7);
+        assert_eq!(tree.sum_of_distances(), vec![0]);
+        assert_eq!(tree.eccentricities(), vec![0]);
+    }
+
+    #[test]
+    fn test_random_trees() {
+        let mut generator = TreeGenerator::new(3);
+        for n in 1..60 {
+            let tree = generator.tree(n, Shape::RandomBinary, 0u32..=0);
+
+            let (sums, eccentricities) = brute_force(&tree);
+            assert_eq!(tree.sum_of_distances(), sums);
+            assert_eq!(tree.eccentricities(), eccentricities);
+        }
+    }
+
+    /// Number of nodes with an odd key within distance 1, a custom rerooting
+    /// that uses the keys
+    struct OddNeighbours;
+
+    impl Rerooting<u32> for OddNeighbours {
+        // (is the node odd, odd nodes within distance 1 of the root)
+        type Value = (usize, usize);
+
+        fn identity(&self) -> (usize, usize) {
+            (0, 0)
+        }
+
+        fn merge(&self, a: &(usize, usize), b: &(usize, usize)) -> (usize, usize) {
+            (0, a.1 + b.1)
+        }
+
+        fn add_edge(&self, &(odd, _): &(usize, usize)) -> (usize, usize) {
+            (0, odd)
+        }
+
+        fn add_vertex(&self, &(_, near): &(usize, usize), _: usize, key: &u32) -> (usize, usize) {
+            let odd = (key % 2) as usize;
+            (odd, near + odd)
+        }
+    }
+
+    #[test]
+    fn test_custom_rerooting() {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 3, true); // id 1
+        tree.add_node(0, 4, false); // id 2
+        tree.add_node(1, 5, true); // id 3
+        tree.add_node(2, 7, false); // id 4
+
+        //       1
+        //     /   \
+        //    3     4
+        //   /       \
+        //  5         7
+
+        let near: Vec<usize> = tree
+            .reroot(&OddNeighbours)
+            .into_iter()
+            .map(|(_, near)| near)
+            .collect();
+        assert_eq!(near, vec![2, 3, 2, 2, 1]);
+    }
+}
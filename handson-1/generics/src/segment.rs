@@ -0,0 +1,216 @@
+use std::ops::Add;
+
+/// Sum and maximum of a non-empty range of keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeAggregate<T> {
+    pub sum: T,
+    pub max: T,
+}
+
+impl<T: Ord + Add<Output = T> + Copy> RangeAggregate<T> {
+    fn leaf(key: T) -> Self {
+        RangeAggregate { sum: key, max: key }
+    }
+
+    /// Merges the aggregates of two adjacent ranges
+    pub(crate) fn merge(self, other: Self) -> Self {
+        RangeAggregate {
+            sum: self.sum + other.sum,
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+/// Recursive array segment tree with point updates, like the one of the
+/// min-and-max exercise, storing the sum and the maximum of every segment.
+/// The children of the node `i` are `2i + 1` and `2i + 2`.
+pub(crate) struct SegmentTree<T> {
+    tree: Vec<Option<RangeAggregate<T>>>,
+    len: usize,
+}
+
+impl<T: Ord + Add<Output = T> + Copy> SegmentTree<T> {
+    /// Builds the segment tree of `keys`, the positions are the indices of the slice
+    pub(crate) fn new(keys: &[T]) -> Self {
+        // 4 * n is enough to hold the perfect tree over n leaves
+        let mut segment_tree = SegmentTree {
+            tree: vec![None; 4 * keys.len()],
+            len: keys.len(),
+        };
+        if !keys.is_empty() {
+            segment_tree.build(keys, 0, keys.len() - 1, 0);
+        }
+        segment_tree
+    }
+
+    fn build(&mut self, keys: &[T], left_bound: usize, right_bound: usize, curr_index: usize) {
+        // Populate the leaves with the keys
+        if left_bound == right_bound {
+            self.tree[curr_index] = Some(RangeAggregate::leaf(keys[left_bound]));
+            return;
+        }
+        let mid = Self::mid(left_bound, right_bound);
+        self.build(keys, left_bound, mid, Self::left(curr_index));
+        self.build(keys, mid + 1, right_bound, Self::right(curr_index));
+        self.pull(curr_index);
+    }
+
+    /// Returns the key at `position`
+    pub(crate) fn get(&self, position: usize) -> T {
+        self.query(position, position)
+            .expect("Position out of range")
+            .sum
+    }
+
+    /// Replaces the key at `position` with `key`
+    pub(crate) fn set(&mut self, position: usize, key: T) {
+        assert!(position < self.len, "Position out of range");
+        self.set_query(0, position, 0, self.len - 1, key);
+    }
+
+    fn set_query(
+        &mut self,
+        curr_index: usize,
+        position: usize,
+        left_bound: usize,
+        right_bound: usize,
+        key: T,
+    ) {
+        if left_bound == right_bound {
+            self.tree[curr_index] = Some(RangeAggregate::leaf(key));
+            return;
+        }
+        let mid = Self::mid(left_bound, right_bound);
+        if position <= mid {
+            self.set_query(Self::left(curr_index), position, left_bound, mid, key);
+        } else {
+            self.set_query(Self::right(curr_index), position, mid + 1, right_bound, key);
+        }
+        self.pull(curr_index);
+    }
+
+    /// Returns the aggregate of the positions in `[left_query, right_query]`,
+    /// None if the range is outside the tree
+    pub(crate) fn query(&self, left_query: usize, right_query: usize) -> Option<RangeAggregate<T>> {
+        assert!(left_query <= right_query, "Invalid query range");
+        if self.len == 0 {
+            return None;
+        }
+        self.range_query(0, left_query, right_query, 0, self.len - 1)
+    }
+
+    fn range_query(
+        &self,
+        curr_index: usize,
+        left_query: usize,
+        right_query: usize,
+        left_bound: usize,
+        right_bound: usize,
+    ) -> Option<RangeAggregate<T>> {
+        // No overlap
+        if left_query > right_bound || right_query < left_bound {
+            return None;
+        }
+
+        // Total overlap
+        if left_query <= left_bound && right_query >= right_bound {
+            return self.tree[curr_index];
+        }
+
+        // Partial overlap
+        let mid = Self::mid(left_bound, right_bound);
+        let left = self.range_query(
+            Self::left(curr_index),
+            left_query,
+            right_query,
+            left_bound,
+            mid,
+        );
+        let right = self.range_query(
+            Self::right(curr_index),
+            left_query,
+            right_query,
+            mid + 1,
+            right_bound,
+        );
+        merge(left, right)
+    }
+
+    /// Recomputes a node from its children
+    fn pull(&mut self, curr_index: usize) {
+        self.tree[curr_index] = merge(
+            self.tree[Self::left(curr_index)],
+            self.tree[Self::right(curr_index)],
+        );
+    }
+
+    fn mid(left: usize, right: usize) -> usize {
+        // Prevent overflow
+        left + (right - left) / 2
+    }
+
+    fn left(curr_index: usize) -> usize {
+        (2 * curr_index) + 1
+    }
+
+    fn right(curr_index: usize) -> usize {
+        (2 * curr_index) + 2
+    }
+}
+
+/// Merges two optional aggregates, None is the empty range
+pub(crate) fn merge<T: Ord + Add<Output = T> + Copy>(
+    a: Option<RangeAggregate<T>>,
+    b: Option<RangeAggregate<T>>,
+) -> Option<RangeAggregate<T>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.merge(b)),
+        _ => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query() {
+        let segment_tree = SegmentTree::new(&[5, -2, 7, 1, 3]);
+
+        assert_eq!(
+            segment_tree.query(0, 4),
+            Some(RangeAggregate { sum: 14, max: 7 })
+        );
+        assert_eq!(
+            segment_tree.query(1, 1),
+            Some(RangeAggregate { sum: -2, max: -2 })
+        );
+        assert_eq!(
+            segment_tree.query(3, 4),
+            Some(RangeAggregate { sum: 4, max: 3 })
+        );
+        assert_eq!(segment_tree.query(5, 8), None);
+    }
+
+    #[test]
+    fn test_set() {
+        let mut segment_tree = SegmentTree::new(&[5, -2, 7, 1, 3]);
+        segment_tree.set(2, -10);
+
+        assert_eq!(segment_tree.get(2), -10);
+        assert_eq!(
+            segment_tree.query(0, 4),
+            Some(RangeAggregate { sum: -3, max: 5 })
+        );
+        assert_eq!(
+            segment_tree.query(2, 4),
+            Some(RangeAggregate { sum: -6, max: 3 })
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        let segment_tree = SegmentTree::<i32>::new(&[]);
+        assert_eq!(segment_tree.query(0, 0), None);
+    }
+}
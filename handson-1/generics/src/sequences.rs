@@ -0,0 +1,283 @@
+use std::{collections::HashMap, fmt, hash::Hash, ops::Add};
+
+use crate::Tree;
+
+/// Error returned when a tree can't be rebuilt from two traversal sequences.
+/// `position` is the 0-based index in the pre-order or post-order sequence,
+/// or in the in-order sequence for a key that appears twice in it.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SequenceError {
+    /// The two sequences have different lengths
+    LengthMismatch { first: usize, inorder: usize },
+    /// A key appears more than once
+    DuplicateKey { position: usize },
+    /// A key is not in the in-order sequence
+    UnknownKey { position: usize },
+    /// A key is in the in-order sequence, but outside the subtree it should belong to
+    Inconsistent { position: usize },
+}
+
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SequenceError::LengthMismatch { first, inorder } => write!(
+                f,
+                "The sequences have different lengths: {} and {}",
+                first, inorder
+            ),
+            SequenceError::DuplicateKey { position } => {
+                write!(f, "Position {}: duplicate key", position)
+            }
+            SequenceError::UnknownKey { position } => {
+                write!(
+                    f,
+                    "Position {}: the key is not in the in-order sequence",
+                    position
+                )
+            }
+            SequenceError::Inconsistent { position } => {
+                write!(f, "Position {}: the sequences are inconsistent", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SequenceError {}
+
+impl<T: Copy> Tree<T> {
+    /// Returns the keys in pre-order
+    pub fn preorder_keys(&self) -> Vec<T> {
+        self.iter_preorder().map(|(_, &key)| key).collect()
+    }
+
+    /// Returns the keys in in-order
+    pub fn inorder_keys(&self) -> Vec<T> {
+        self.iter_inorder().map(|(_, &key)| key).collect()
+    }
+
+    /// Returns the keys in post-order
+    pub fn postorder_keys(&self) -> Vec<T> {
+        self.iter_postorder().map(|(_, &key)| key).collect()
+    }
+}
+
+impl<T: Ord + Add<Output = T> + Default + Copy + Hash> Tree<T> {
+    /// Rebuilds the tree from its pre-order and in-order sequences of keys, in O(n).
+    /// The keys must be distinct. The nodes are numbered in pre-order.
+    pub fn from_pre_in(preorder: &[T], inorder: &[T]) -> Result<Self, SequenceError> {
+        Self::from_sequence(preorder.iter().copied().enumerate(), inorder, false)
+    }
+
+    /// Rebuilds the tree from its post-order and in-order sequences of keys, in O(n).
+    /// The keys must be distinct. The nodes are numbered in reverse post-order.
+    pub fn from_post_in(postorder: &[T], inorder: &[T]) -> Result<Self, SequenceError> {
+        Self::from_sequence(postorder.iter().copied().enumerate().rev(), inorder, true)
+    }
+
+    /// Builds the tree taking the keys of `sequence` as the roots of the subtrees,
+    /// every root before its subtrees: the left one first, or the right one first
+    /// if `right_first`. Each item is `(position, key)`.
+    /// The position of a root in `inorder` splits its range in the ranges of the
+    /// left and the right subtree.
+    fn from_sequence(
+        sequence: impl ExactSizeIterator<Item = (usize, T)>,
+        inorder: &[T],
+        right_first: bool,
+    ) -> Result<Self, SequenceError> {
+        if sequence.len() != inorder.len() {
+            return Err(SequenceError::LengthMismatch {
+                first: sequence.len(),
+                inorder: inorder.len(),
+            });
+        }
+
+        // Position of every key in the in-order sequence
+        let mut index = HashMap::with_capacity(inorder.len());
+        for (position, key) in inorder.iter().enumerate() {
+            if index.insert(key, position).is_some() {
+                return Err(SequenceError::DuplicateKey { position });
+            }
+        }
+
+        let mut tree = Tree::new();
+        let mut used = vec![false; inorder.len()];
+        // Subtrees still to build, as (in-order range start, end excluded, parent, is_left).
+        // They are popped in the same order of the roots in the sequence.
+        let mut stack: Vec<(usize, usize, Option<usize>, bool)> =
+            vec![(0, inorder.len(), None, false)];
+
+        for (position, key) in sequence {
+            // Skip the empty subtrees, the sequence has no root for them
+            let (start, end, parent, is_left) = loop {
+                match stack.pop() {
+                    Some((start, end, ..)) if start == end => continue,
+                    Some(frame) => break frame,
+                    None => return Err(SequenceError::Inconsistent { position }),
+                }
+            };
+
+            let &middle = index
+                .get(&key)
+                .ok_or(SequenceError::UnknownKey { position })?;
+            if used[middle] {
+                return Err(SequenceError::DuplicateKey { position });
+            }
+            if middle < start || middle >= end {
+                return Err(SequenceError::Inconsistent { position });
+            }
+            used[middle] = true;
+
+            let id = match parent {
+                None => {
+                    tree = Tree::with_root(key);
+                    0
+                }
+                Some(parent) => tree.attach(parent, key, is_left),
+            };
+
+            let left = (start, middle, Some(id), true);
+            let right = (middle + 1, end, Some(id), false);
+            if right_first {
+                stack.extend([left, right]);
+            } else {
+                stack.extend([right, left]);
+            }
+        }
+        if let Some(root) = tree.root {
+            tree.count_attached(root);
+        }
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeId, Shape, TreeGenerator};
+
+    fn tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 2, true); // id 1
+        tree.add_node(0, 3, false); // id 2
+        tree.add_node(1, 4, true); // id 3
+        tree.add_node(1, 5, false); // id 4
+        tree.add_node(4, 6, true); // id 5
+        tree.add_node(2, 7, false); // id 6
+
+        //        1
+        //      /   \
+        //     2     3
+        //    / \     \
+        //   4   5     7
+        //      /
+        //     6
+        tree
+    }
+
+    #[test]
+    fn test_exporters() {
+        let tree = tree();
+
+        assert_eq!(tree.preorder_keys(), vec![1, 2, 4, 5, 6, 3, 7]);
+        assert_eq!(tree.inorder_keys(), vec![4, 2, 6, 5, 1, 3, 7]);
+        assert_eq!(tree.postorder_keys(), vec![4, 6, 5, 2, 7, 3, 1]);
+    }
+
+    #[test]
+    fn test_from_pre_in() {
+        let tree = tree();
+        let rebuilt = Tree::from_pre_in(&tree.preorder_keys(), &tree.inorder_keys()).unwrap();
+
+        assert!(rebuilt.is_isomorphic(&tree));
+        // The nodes are numbered in pre-order
+        assert_eq!(
+            rebuilt
+                .iter_preorder()
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>(),
+            (0..7).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_from_post_in() {
+        let tree = tree();
+        let rebuilt = Tree::from_post_in(&tree.postorder_keys(), &tree.inorder_keys()).unwrap();
+
+        assert!(rebuilt.is_isomorphic(&tree));
+        assert_eq!(rebuilt.postorder_keys(), tree.postorder_keys());
+    }
+
+    #[test]
+    fn test_empty_and_single() {
+        assert!(Tree::<i32>::from_pre_in(&[], &[]).unwrap().is_empty());
+        assert!(Tree::<i32>::from_post_in(&[], &[]).unwrap().is_empty());
+
+        let tree = Tree::from_pre_in(&[7], &[7]).unwrap();
+        assert_eq!(tree.to_level_order(), "[7]");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Tree::from_pre_in(&[1, 2], &[1]).err(),
+            Some(SequenceError::LengthMismatch {
+                first: 2,
+                inorder: 1
+            })
+        );
+        assert_eq!(
+            Tree::from_pre_in(&[1, 2, 3], &[2, 1, 2]).err(),
+            Some(SequenceError::DuplicateKey { position: 2 })
+        );
+        assert_eq!(
+            Tree::from_pre_in(&[1, 1, 3], &[2, 1, 3]).err(),
+            Some(SequenceError::DuplicateKey { position: 1 })
+        );
+        assert_eq!(
+            Tree::from_pre_in(&[1, 9, 3], &[2, 1, 3]).err(),
+            Some(SequenceError::UnknownKey { position: 1 })
+        );
+        // 3 is on the right of the root 2 in in-order, but the next root in
+        // pre-order must be the one of the left subtree
+        assert_eq!(
+            Tree::from_pre_in(&[2, 3, 1], &[1, 2, 3]).err(),
+            Some(SequenceError::Inconsistent { position: 1 })
+        );
+        assert_eq!(
+            Tree::from_post_in(&[1, 2, 3], &[2, 3, 1]).err(),
+            Some(SequenceError::Inconsistent { position: 1 })
+        );
+    }
+
+    #[test]
+    fn test_random_round_trips() {
+        let mut generator = TreeGenerator::new(7);
+        for n in 0..60 {
+            // A random shape with distinct keys, the node ids
+            let mut tree = generator.tree(n, Shape::RandomBinary, 0u32..=0);
+            for id in 0..n {
+                *tree.key_mut(NodeId::from(id)) = id as u32;
+            }
+
+            let inorder = tree.inorder_keys();
+            let from_pre = Tree::from_pre_in(&tree.preorder_keys(), &inorder).unwrap();
+            let from_post = Tree::from_post_in(&tree.postorder_keys(), &inorder).unwrap();
+            assert!(from_pre.is_isomorphic(&tree));
+            assert!(from_post.is_isomorphic(&tree));
+        }
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let n = 200_000;
+        let inorder: Vec<u32> = (0..n).collect();
+        let preorder: Vec<u32> = (0..n).collect();
+
+        // Every node is the right child of the previous one
+        let tree = Tree::from_pre_in(&preorder, &inorder).unwrap();
+        assert_eq!(tree.postorder_keys(), (0..n).rev().collect::<Vec<_>>());
+        let tree = Tree::from_post_in(&tree.postorder_keys(), &inorder).unwrap();
+        assert_eq!(tree.preorder_keys(), preorder);
+    }
+}
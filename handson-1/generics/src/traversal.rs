@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+
+use crate::Tree;
+
+impl<T> Tree<T> {
+    /// Returns an iterator over the nodes in pre-order (node, left, right).
+    /// Each item is the pair `(node_id, &key)`.
+    pub fn iter_preorder(&self) -> Preorder<'_, T> {
+        Preorder {
+            tree: self,
+            stack: self.root.into_iter().collect(),
+        }
+    }
+
+    /// Returns an iterator over the nodes in in-order (left, node, right).
+    /// Each item is the pair `(node_id, &key)`.
+    pub fn iter_inorder(&self) -> Inorder<'_, T> {
+        Inorder {
+            tree: self,
+            stack: Vec::new(),
+            current: self.root,
+        }
+    }
+
+    /// Returns an iterator over the nodes in post-order (left, right, node).
+    /// Each item is the pair `(node_id, &key)`.
+    pub fn iter_postorder(&self) -> Postorder<'_, T> {
+        Postorder {
+            tree: self,
+            stack: self.root.map(|id| (id, false)).into_iter().collect(),
+        }
+    }
+
+    /// Returns an iterator over the nodes level by level, from left to right.
+    /// Each item is the pair `(node_id, &key)`.
+    pub fn iter_level_order(&self) -> LevelOrder<'_, T> {
+        LevelOrder {
+            tree: self,
+            queue: self.root.into_iter().collect(),
+        }
+    }
+
+    /// Returns the pair `(node_id, &key)` yielded by the iterators
+    fn item(&self, node_id: usize) -> (usize, &T) {
+        (node_id, &self.nodes[node_id].key)
+    }
+
+    /// Returns the ids of the children of `node_id`, left first
+    pub(crate) fn children(&self, node_id: usize) -> impl Iterator<Item = usize> {
+        let node = &self.nodes[node_id];
+        node.id_left.into_iter().chain(node.id_right)
+    }
+}
+
+/// Pre-order iterator returned by `Tree::iter_preorder`
+pub struct Preorder<'a, T> {
+    tree: &'a Tree<T>,
+    stack: Vec<usize>,
+}
+
+impl<'a, T> Iterator for Preorder<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        let node = &self.tree.nodes[id];
+
+        // Push the right child first, so that the left one is visited first
+        self.stack.extend(node.id_right);
+        self.stack.extend(node.id_left);
+
+        Some(self.tree.item(id))
+    }
+}
+
+/// In-order iterator returned by `Tree::iter_inorder`
+pub struct Inorder<'a, T> {
+    tree: &'a Tree<T>,
+    stack: Vec<usize>,
+    // Next subtree to descend into before popping from the stack
+    current: Option<usize>,
+}
+
+impl<'a, T> Iterator for Inorder<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Go down to the leftmost node of the current subtree
+        while let Some(id) = self.current {
+            self.stack.push(id);
+            self.current = self.tree.nodes[id].id_left;
+        }
+
+        let id = self.stack.pop()?;
+        self.current = self.tree.nodes[id].id_right;
+
+        Some(self.tree.item(id))
+    }
+}
+
+/// Post-order iterator returned by `Tree::iter_postorder`
+pub struct Postorder<'a, T> {
+    tree: &'a Tree<T>,
+    // Pairs of (node_id, children already pushed)
+    stack: Vec<(usize, bool)>,
+}
+
+impl<'a, T> Iterator for Postorder<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((id, expanded)) = self.stack.pop() {
+            if expanded {
+                return Some(self.tree.item(id));
+            }
+
+            // The node is yielded after both of its children
+            let node = &self.tree.nodes[id];
+            self.stack.push((id, true));
+            self.stack.extend(node.id_right.map(|right| (right, false)));
+            self.stack.extend(node.id_left.map(|left| (left, false)));
+        }
+        None
+    }
+}
+
+/// Level-order iterator returned by `Tree::iter_level_order`
+pub struct LevelOrder<'a, T> {
+    tree: &'a Tree<T>,
+    queue: VecDeque<usize>,
+}
+
+impl<'a, T> Iterator for LevelOrder<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.queue.pop_front()?;
+        let node = &self.tree.nodes[id];
+
+        self.queue.extend(node.id_left);
+        self.queue.extend(node.id_right);
+
+        Some(self.tree.item(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys<'a>(iter: impl Iterator<Item = (usize, &'a i32)>) -> Vec<i32> {
+        iter.map(|(_, key)| *key).collect()
+    }
+
+    fn balanced_tree() -> Tree<i32> {
+        let mut tree = Tree::with_root(40);
+        tree.add_node(0, 30, true); // id 1
+        tree.add_node(0, 50, false); // id 2
+        tree.add_node(1, 25, true); // id 3
+        tree.add_node(1, 35, false); // id 4
+        tree.add_node(2, 45, true); // id 5
+        tree.add_node(2, 60, false); // id 6
+
+        //       40
+        //     /    \
+        //   30      50
+        //  / \     / \
+        // 25 35  45  60
+        tree
+    }
+
+    #[test]
+    fn test_preorder() {
+        let tree = balanced_tree();
+        assert_eq!(keys(tree.iter_preorder()), vec![40, 30, 25, 35, 50, 45, 60]);
+    }
+
+    #[test]
+    fn test_inorder() {
+        let tree = balanced_tree();
+        assert_eq!(keys(tree.iter_inorder()), vec![25, 30, 35, 40, 45, 50, 60]);
+    }
+
+    #[test]
+    fn test_postorder() {
+        let tree = balanced_tree();
+        assert_eq!(
+            keys(tree.iter_postorder()),
+            vec![25, 35, 30, 45, 60, 50, 40]
+        );
+    }
+
+    #[test]
+    fn test_level_order() {
+        let tree = balanced_tree();
+        let ids: Vec<usize> = tree.iter_level_order().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_unbalanced_tree() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(1, 3, true); // id 2
+        tree.add_node(2, 4, false); // id 3
+
+        //       10
+        //      /
+        //     5
+        //    /
+        //   3
+        //    \
+        //     4
+
+        assert_eq!(keys(tree.iter_preorder()), vec![10, 5, 3, 4]);
+        assert_eq!(keys(tree.iter_inorder()), vec![3, 4, 5, 10]);
+        assert_eq!(keys(tree.iter_postorder()), vec![4, 3, 5, 10]);
+        assert_eq!(keys(tree.iter_level_order()), vec![10, 5, 3, 4]);
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let mut tree = Tree::with_root(10);
+        tree.remove_subtree(0);
+
+        assert_eq!(tree.iter_preorder().count(), 0);
+        assert_eq!(tree.iter_inorder().count(), 0);
+        assert_eq!(tree.iter_postorder().count(), 0);
+        assert_eq!(tree.iter_level_order().count(), 0);
+    }
+
+    #[test]
+    fn test_custom_pass() {
+        let tree = balanced_tree();
+
+        // Sum of the keys on the leaves
+        let leaves: i32 = tree
+            .iter_postorder()
+            .filter(|&(id, _)| {
+                tree.nodes[id].id_left.is_none() && tree.nodes[id].id_right.is_none()
+            })
+            .map(|(_, key)| key)
+            .sum();
+        assert_eq!(leaves, 165);
+    }
+}
@@ -1,7 +1,52 @@
+/// Error returned by the checked aggregates when the result doesn't fit in a `u32`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowError;
+
+impl std::fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The sum of the keys overflows")
+    }
+}
+
+impl std::error::Error for OverflowError {}
+
+/// Error returned by the fallible operations on a tree, e.g. `try_add_node`.
+/// The panicking operations panic with the same message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeError {
+    /// The parent of the new node is out of range or has been removed
+    ParentNotFound { parent_id: usize },
+    /// The parent has the child on that side already set
+    ChildAlreadySet { parent_id: usize, is_left: bool },
+    /// The node id is out of range or has been removed
+    NodeNotFound { node_id: usize },
+}
+
+impl std::fmt::Display for TreeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeError::ParentNotFound { .. } => write!(f, "Parent node id does not exist"),
+            TreeError::ChildAlreadySet { is_left: true, .. } => {
+                write!(f, "Parent node has the left child already set")
+            }
+            TreeError::ChildAlreadySet { is_left: false, .. } => {
+                write!(f, "Parent node has the right child already set")
+            }
+            TreeError::NodeNotFound { .. } => write!(f, "Node id does not exist"),
+        }
+    }
+}
+
+impl std::error::Error for TreeError {}
+
 pub struct Node {
     key: u32,
     id_left: Option<usize>,
     id_right: Option<usize>,
+    // None for the root
+    id_parent: Option<usize>,
+    // false when the slot has been reclaimed and sits in the free list
+    alive: bool,
 }
 
 impl Node {
@@ -10,18 +55,26 @@ impl Node {
             key,
             id_left: None,
             id_right: None,
+            id_parent: None,
+            alive: true,
         }
     }
 }
 
 pub struct Tree {
     nodes: Vec<Node>,
+    // None when the whole tree has been removed
+    root: Option<usize>,
+    // Ids of the reclaimed slots, reused by the next insertions
+    free: Vec<usize>,
 }
 
 impl Tree {
     pub fn with_root(key: u32) -> Self {
         Self {
             nodes: vec![Node::new(key)],
+            root: Some(0),
+            free: Vec::new(),
         }
     }
 
@@ -31,26 +84,35 @@ impl Tree {
     ///
     /// # Panics
     /// Panics if the `parent_id` does not exist, or if the node `parent_id ` has  
-    /// the child already set.
+    /// the child already set. `try_add_node` returns the error instead.
     pub fn add_node(&mut self, parent_id: usize, key: u32, is_left: bool) -> usize {
-        assert!(
-            parent_id < self.nodes.len(),
-            "Parent node id does not exist"
-        );
-        if is_left {
-            assert!(
-                self.nodes[parent_id].id_left.is_none(),
-                "Parent node has the left child already set"
-            );
+        self.try_add_node(parent_id, key, is_left)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Adds a child to the node with `parent_id` like `add_node`.
+    /// It returns an error if the `parent_id` does not exist, or if the node
+    /// `parent_id` has the child already set. In that case the tree is not modified.
+    pub fn try_add_node(
+        &mut self,
+        parent_id: usize,
+        key: u32,
+        is_left: bool,
+    ) -> Result<usize, TreeError> {
+        if !self.is_live(parent_id) {
+            return Err(TreeError::ParentNotFound { parent_id });
+        }
+        let child = if is_left {
+            self.nodes[parent_id].id_left
         } else {
-            assert!(
-                self.nodes[parent_id].id_right.is_none(),
-                "Parent node has the right child already set"
-            );
+            self.nodes[parent_id].id_right
+        };
+        if child.is_some() {
+            return Err(TreeError::ChildAlreadySet { parent_id, is_left });
         }
 
-        let child_id = self.nodes.len();
-        self.nodes.push(Node::new(key));
+        let child_id = self.alloc(key);
+        self.nodes[child_id].id_parent = Some(parent_id);
 
         let child = if is_left {
             &mut self.nodes[parent_id].id_left
@@ -60,74 +122,243 @@ impl Tree {
 
         *child = Some(child_id);
 
-        child_id
+        Ok(child_id)
     }
 
-    /// Returns the sum of all the keys in the tree
-    pub fn sum(&self) -> u32 {
-        self.rec_sum(Some(0))
+    /// Removes the subtree rooted at `node_id` and returns the number of removed nodes.
+    /// The slots of the removed nodes are reclaimed and reused by the next insertions,
+    /// the ids of the remaining nodes do not change.
+    /// Removing the root leaves the tree empty.
+    ///
+    /// # Panics
+    /// Panics if the `node_id` does not exist. `try_remove_subtree` returns the error instead.
+    pub fn remove_subtree(&mut self, node_id: usize) -> usize {
+        self.try_remove_subtree(node_id)
+            .unwrap_or_else(|err| panic!("{}", err))
     }
 
-    /// A private recursive function that computes the sum of
-    /// nodes in the subtree rooted at `node_id`.
-    fn rec_sum(&self, node_id: Option<usize>) -> u32 {
-        if let Some(id) = node_id {
-            assert!(id < self.nodes.len(), "Node id is out of range");
-            let node = &self.nodes[id];
+    /// Removes the subtree rooted at `node_id` like `remove_subtree`.
+    /// It returns an error if the `node_id` does not exist.
+    pub fn try_remove_subtree(&mut self, node_id: usize) -> Result<usize, TreeError> {
+        if !self.is_live(node_id) {
+            return Err(TreeError::NodeNotFound { node_id });
+        }
+        self.unlink(node_id);
+
+        let mut removed = 0;
+        let mut stack = vec![node_id];
+        while let Some(id) = stack.pop() {
+            let node = &mut self.nodes[id];
+            stack.extend(node.id_left.take());
+            stack.extend(node.id_right.take());
+            node.id_parent = None;
+            node.alive = false;
+            self.free.push(id);
+            removed += 1;
+        }
+        Ok(removed)
+    }
 
-            let sum_left = self.rec_sum(node.id_left);
-            let sum_right = self.rec_sum(node.id_right);
+    /// Detaches the subtree rooted at `node_id` and returns it as a new tree.
+    /// In the new tree the nodes are renumbered in pre-order, so the detached
+    /// root has id 0. The slots are reclaimed as in `remove_subtree`.
+    ///
+    /// # Panics
+    /// Panics if the `node_id` does not exist. `try_detach` returns the error instead.
+    pub fn detach(&mut self, node_id: usize) -> Tree {
+        self.try_detach(node_id)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
 
-            return sum_left + sum_right + node.key;
+    /// Detaches the subtree rooted at `node_id` like `detach`.
+    /// It returns an error if the `node_id` does not exist.
+    pub fn try_detach(&mut self, node_id: usize) -> Result<Tree, TreeError> {
+        if !self.is_live(node_id) {
+            return Err(TreeError::NodeNotFound { node_id });
+        }
+
+        let mut subtree = Tree::with_root(self.nodes[node_id].key);
+        // Pairs of (id in self, id in the subtree)
+        let mut stack = vec![(node_id, 0)];
+        while let Some((old_id, new_id)) = stack.pop() {
+            let (left, right) = (self.nodes[old_id].id_left, self.nodes[old_id].id_right);
+            // Push the right child first to visit the left one first
+            if let Some(right) = right {
+                let id = subtree.add_node(new_id, self.nodes[right].key, false);
+                stack.push((right, id));
+            }
+            if let Some(left) = left {
+                let id = subtree.add_node(new_id, self.nodes[left].key, true);
+                stack.push((left, id));
+            }
         }
-        0
+
+        self.remove_subtree(node_id);
+        Ok(subtree)
     }
 
-    /// Returns if the tree is a binary search tree or not
-    pub fn is_bst(&self) -> bool {
-        self.rec_bst(Some(0), &mut None)
+    /// Returns if `node_id` is the id of a node currently in the tree
+    fn is_live(&self, node_id: usize) -> bool {
+        node_id < self.nodes.len() && self.nodes[node_id].alive
+    }
+
+    /// Stores a new node with the given `key`, reusing a reclaimed slot if there is one.
+    fn alloc(&mut self, key: u32) -> usize {
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = Node::new(key);
+                id
+            }
+            None => {
+                self.nodes.push(Node::new(key));
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Clears the link that points to `node_id`, either from its parent or from the root.
+    fn unlink(&mut self, node_id: usize) {
+        match self.nodes[node_id].id_parent.take() {
+            None => self.root = None,
+            Some(parent_id) => {
+                let parent = &mut self.nodes[parent_id];
+                if parent.id_left == Some(node_id) {
+                    parent.id_left = None;
+                } else {
+                    parent.id_right = None;
+                }
+            }
+        }
+    }
+
+    /// Returns the sum of all the keys in the tree
+    ///
+    /// # Panics
+    /// Panics in debug builds if the sum overflows `u32`, use `checked_sum`,
+    /// `saturating_sum` or `wide_sum` when it can happen.
+    pub fn sum(&self) -> u32 {
+        // The addition never fails, so the Option is always Some
+        self.fold_sum(|key| key, |a, b| Some(a + b)).unwrap()
+    }
+
+    /// Returns the sum of all the keys, or an error if it overflows `u32`
+    pub fn checked_sum(&self) -> Result<u32, OverflowError> {
+        self.fold_sum(|key| key, u32::checked_add)
+            .ok_or(OverflowError)
     }
 
-    /// Auxiliary function to check if the tree is a binary search tree with the in-order visit.
-    /// The parameters are the current node and a reference to value of the previous node
-    /// An alternative is to use a reference to the index of the previous node.
-    fn rec_bst(&self, current_node: Option<usize>, previous_node: &mut Option<u32>) -> bool {
-        if let Some(current_id) = current_node {
-            assert!(current_id < self.nodes.len(), "Node id is out of range");
+    /// Returns the sum of all the keys, clamped to `u32::MAX`
+    pub fn saturating_sum(&self) -> u32 {
+        self.fold_sum(|key| key, |a, b| Some(a.saturating_add(b)))
+            .unwrap()
+    }
+
+    /// Returns the sum of all the keys accumulated in a `u64`, that can't overflow
+    /// with less than 2^32 nodes
+    pub fn wide_sum(&self) -> u64 {
+        self.fold_sum(u64::from, |a, b| Some(a + b)).unwrap()
+    }
 
-            let tree = &self.nodes;
+    /// Sums the keys converted by `widen` with `add`, visiting the tree with an
+    /// explicit stack. It returns None as soon as `add` returns None.
+    fn fold_sum<S: Default>(
+        &self,
+        widen: impl Fn(u32) -> S,
+        add: impl Fn(S, S) -> Option<S>,
+    ) -> Option<S> {
+        let mut sum = S::default();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            stack.extend(node.id_left);
+            stack.extend(node.id_right);
+
+            sum = add(sum, widen(node.key))?;
+        }
+        Some(sum)
+    }
 
-            if !self.rec_bst(tree[current_id].id_left, previous_node) {
-                return false;
+    /// Returns if the tree is a binary search tree or not.
+    /// The keys are checked with an iterative in-order visit, comparing each key
+    /// with the key of the previous node.
+    pub fn is_bst(&self) -> bool {
+        let mut previous_key: Option<u32> = None;
+        let mut stack = Vec::new();
+        let mut current_node = self.root;
+
+        loop {
+            // Go down to the leftmost node of the current subtree
+            while let Some(current_id) = current_node {
+                stack.push(current_id);
+                current_node = self.nodes[current_id].id_left;
             }
 
-            if let Some(previous_id) = previous_node {
-                if *previous_id > tree[current_id].key {
+            let Some(current_id) = stack.pop() else {
+                return true;
+            };
+            let node = &self.nodes[current_id];
+
+            if let Some(previous_key) = previous_key {
+                if previous_key > node.key {
                     return false;
                 }
             }
+            previous_key = Some(node.key);
 
-            *previous_node = Some(tree[current_id].key);
-
-            return self.rec_bst(tree[current_id].id_right, previous_node);
+            current_node = node.id_right;
         }
-        true
     }
 
     /// Returns the maximum path sum. If the tree is empty, it returns None.
+    ///
+    /// # Panics
+    /// Panics in debug builds if a path sum overflows `u32`, use `checked_max_path_sum`,
+    /// `saturating_max_path_sum` or `wide_max_path_sum` when it can happen.
     pub fn max_path_sum(&self) -> Option<u32> {
-        self.rec_max_path_sum(Some(0)).0
+        // The addition never fails, so the outer Option is always Some
+        self.fold_max_path_sum(|key| key, |a, b| Some(a + b))
+            .flatten()
     }
 
-    fn rec_max_path_sum(&self, current_node: Option<usize>) -> (Option<u32>, Option<u32>) {
-        if let Some(current_id) = current_node {
-            assert!(current_id < self.nodes.len(), "Node id is out of range");
+    /// Returns the maximum path sum (None if the tree is empty), or an error
+    /// if the sum of any candidate path overflows `u32`
+    pub fn checked_max_path_sum(&self) -> Result<Option<u32>, OverflowError> {
+        self.fold_max_path_sum(|key| key, u32::checked_add)
+            .ok_or(OverflowError)
+    }
 
+    /// Returns the maximum path sum, with the partial sums clamped to `u32::MAX`
+    pub fn saturating_max_path_sum(&self) -> Option<u32> {
+        self.fold_max_path_sum(|key| key, |a, b| Some(a.saturating_add(b)))
+            .flatten()
+    }
+
+    /// Returns the maximum path sum accumulated in a `u64`
+    pub fn wide_max_path_sum(&self) -> Option<u64> {
+        self.fold_max_path_sum(u64::from, |a, b| Some(a + b))
+            .flatten()
+    }
+
+    /// Computes the maximum path sum of the keys converted by `widen`, adding them with `add`.
+    /// It returns None as soon as `add` returns None, Some(None) if the tree is empty.
+    fn fold_max_path_sum<S: Ord + Copy + Default>(
+        &self,
+        widen: impl Fn(u32) -> S,
+        add: impl Fn(S, S) -> Option<S>,
+    ) -> Option<Option<S>> {
+        // For every node: (maximum path sum in the subtree, maximum sum of a path
+        // that goes down from the node)
+        let mut partial: Vec<(Option<S>, Option<S>)> = vec![(None, None); self.nodes.len()];
+
+        for current_id in self.postorder() {
             let node = &self.nodes[current_id];
-            let (left_max, left_sum) = self.rec_max_path_sum(node.id_left);
-            let (right_max, right_sum) = self.rec_max_path_sum(node.id_right);
+            let key = widen(node.key);
+            let (left_max, left_sum) = node.id_left.map_or((None, None), |id| partial[id]);
+            let (right_max, right_sum) = node.id_right.map_or((None, None), |id| partial[id]);
+            let (left_sum, right_sum) = (left_sum.unwrap_or_default(), right_sum.unwrap_or_default());
 
-            let path_sum = Some(node.key + left_sum.unwrap_or(0).max(right_sum.unwrap_or(0)));
+            let path_sum = Some(add(key, left_sum.max(right_sum))?);
 
             // Compute the maximum path sum so far, comparing maximum sum of left path,
             // maximum sum of right path and the sum of the actual path.
@@ -138,14 +369,34 @@ impl Tree {
             let max_sum = left_max
                 .into_iter()
                 .chain(right_max)
-                .chain(Some(
-                    node.key + left_sum.unwrap_or(0) + right_sum.unwrap_or(0),
-                ))
+                .chain(Some(add(add(key, left_sum)?, right_sum)?))
                 .max();
 
-            return (max_sum, path_sum);
+            partial[current_id] = (max_sum, path_sum);
         }
-        (None, None)
+
+        Some(self.root.and_then(|root| partial[root].0))
+    }
+
+    /// Returns the ids of the nodes in post-order, computed with an explicit stack,
+    /// so both children of a node come before the node itself.
+    fn postorder(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.nodes.len());
+        // Pairs of (node_id, children already pushed)
+        let mut stack: Vec<(usize, bool)> = self.root.map(|id| (id, false)).into_iter().collect();
+
+        while let Some((id, expanded)) = stack.pop() {
+            if expanded {
+                order.push(id);
+                continue;
+            }
+
+            let node = &self.nodes[id];
+            stack.push((id, true));
+            stack.extend(node.id_right.map(|right| (right, false)));
+            stack.extend(node.id_left.map(|left| (left, false)));
+        }
+        order
     }
 }
 
@@ -393,4 +644,306 @@ mod tests {
         // Max path should be: 30 -> 15 -> 10 -> 20 -> 30
         assert_eq!(tree.max_path_sum(), Some(105));
     }
+
+    #[test]
+    fn test_remove_subtree() {
+        let mut tree = Tree::with_root(40);
+        tree.add_node(0, 30, true); // id 1
+        tree.add_node(0, 50, false); // id 2
+        tree.add_node(1, 25, true); // id 3
+        tree.add_node(1, 35, false); // id 4
+        tree.add_node(2, 45, true); // id 5
+        tree.add_node(2, 60, false); // id 6
+
+        //       40
+        //     /    \
+        //   30      50
+        //  / \     / \
+        // 25 35  45  60
+
+        assert_eq!(tree.remove_subtree(1), 3);
+
+        //   40
+        //     \
+        //      50
+        //     / \
+        //   45  60
+
+        assert_eq!(tree.sum(), 195);
+        assert!(tree.is_bst());
+        // 45 -> 50 -> 60
+        assert_eq!(tree.max_path_sum(), Some(155));
+
+        // The left slot of the root is free again and the
+        // removed slots are reused by the next insertions
+        let id = tree.add_node(0, 20, true);
+        assert!([1, 3, 4].contains(&id));
+        assert_eq!(tree.sum(), 215);
+
+        // The ids of the remaining nodes are still valid
+        tree.add_node(5, 42, true);
+        assert_eq!(tree.sum(), 257);
+        assert!(tree.is_bst());
+    }
+
+    #[test]
+    fn test_remove_leaf() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(0, 8, false); // id 2
+
+        //     10
+        //    / \
+        //   5   8
+
+        assert!(!tree.is_bst());
+        assert_eq!(tree.remove_subtree(2), 1);
+        assert!(tree.is_bst());
+        assert_eq!(tree.sum(), 15);
+    }
+
+    #[test]
+    fn test_remove_root() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true);
+        tree.add_node(0, 8, false);
+
+        assert_eq!(tree.remove_subtree(0), 3);
+
+        // The tree is empty
+        assert_eq!(tree.sum(), 0);
+        assert!(tree.is_bst());
+        assert_eq!(tree.max_path_sum(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id does not exist")]
+    fn test_remove_twice() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+
+        tree.remove_subtree(1);
+        tree.remove_subtree(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Parent node id does not exist")]
+    fn test_add_node_to_removed_parent() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+
+        tree.remove_subtree(1);
+        tree.add_node(1, 3, true);
+    }
+
+    #[test]
+    fn test_remove_reused_slot() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(1, 3, true); // id 2
+
+        // The slot of 5 is reused under another parent
+        tree.remove_subtree(2);
+        tree.remove_subtree(1);
+        let id = tree.add_node(0, 15, false);
+        let child = tree.add_node(id, 12, true);
+        assert_eq!(tree.sum(), 37);
+
+        //   10
+        //     \
+        //      15
+        //     /
+        //   12
+        assert_eq!(tree.remove_subtree(child), 1);
+        assert_eq!(tree.sum(), 25);
+        assert_eq!(tree.remove_subtree(id), 1);
+        assert_eq!(tree.sum(), 10);
+    }
+
+    #[test]
+    fn test_detach() {
+        let mut tree = Tree::with_root(1);
+        tree.add_node(0, 1, true); // id 1
+        tree.add_node(0, 1, false); // id 2
+        tree.add_node(1, 1, true); // id 3
+        tree.add_node(1, 1, false); // id 4
+        tree.add_node(4, 20, true); // id 5
+        tree.add_node(4, 25, false); // id 6
+
+        //        1
+        //      /  \
+        //     1    1
+        //    / \
+        //   1   1
+        //      / \
+        //    20  25
+
+        let subtree = tree.detach(4);
+
+        //   1
+        //  / \
+        // 20  25
+        assert_eq!(subtree.sum(), 46);
+        assert_eq!(subtree.max_path_sum(), Some(46));
+        assert!(!subtree.is_bst());
+
+        //        1
+        //      /  \
+        //     1    1
+        //    /
+        //   1
+        assert_eq!(tree.sum(), 4);
+        assert_eq!(tree.max_path_sum(), Some(4));
+
+        // The detached tree can grow on its own
+        let mut subtree = subtree;
+        subtree.add_node(1, 7, true);
+        assert_eq!(subtree.sum(), 53);
+    }
+
+    /// Builds a degenerate tree where every node is the child of the previous one
+    fn chain(n: usize, key: u32, is_left: bool) -> Tree {
+        let mut tree = Tree::with_root(key);
+        let mut last = 0;
+        for _ in 1..n {
+            last = tree.add_node(last, key, is_left);
+        }
+        tree
+    }
+
+    #[test]
+    fn test_sum_million_nodes_chain() {
+        let tree = chain(1_000_000, 3, false);
+        assert_eq!(tree.sum(), 3_000_000);
+    }
+
+    #[test]
+    fn test_is_bst_million_nodes_chain() {
+        // Equal keys are allowed by the in-order check
+        assert!(chain(1_000_000, 1, false).is_bst());
+        assert!(chain(1_000_000, 1, true).is_bst());
+
+        // A decreasing right chain is not a BST
+        let mut tree = Tree::with_root(1_000_000);
+        let mut last = 0;
+        for key in (0..1_000_000).rev() {
+            last = tree.add_node(last, key, false);
+        }
+        assert!(!tree.is_bst());
+    }
+
+    #[test]
+    fn test_max_path_sum_million_nodes_chain() {
+        assert_eq!(chain(1_000_000, 2, false).max_path_sum(), Some(2_000_000));
+        assert_eq!(chain(1_000_000, 2, true).max_path_sum(), Some(2_000_000));
+    }
+
+    #[test]
+    fn test_remove_leaves_million_nodes_chain() {
+        let n = 1_000_000;
+        let mut tree = chain(n, 1, false);
+
+        // Each removal only touches the parent of the leaf
+        for id in (1..n).rev() {
+            assert_eq!(tree.remove_subtree(id), 1);
+        }
+        assert_eq!(tree.sum(), 1);
+    }
+
+    fn large_tree() -> Tree {
+        let mut tree = Tree::with_root(u32::MAX / 2);
+        tree.add_node(0, u32::MAX / 2, true); // id 1
+        tree.add_node(0, 10, false); // id 2
+
+        //      MAX/2
+        //      /   \
+        //   MAX/2   10
+        tree
+    }
+
+    #[test]
+    fn test_checked_sum() {
+        assert_eq!(large_tree().checked_sum(), Err(OverflowError));
+
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true);
+        assert_eq!(tree.checked_sum(), Ok(15));
+    }
+
+    #[test]
+    fn test_checked_max_path_sum() {
+        assert_eq!(large_tree().checked_max_path_sum(), Err(OverflowError));
+
+        let mut tree = Tree::with_root(u32::MAX / 2);
+        tree.add_node(0, u32::MAX / 2, true);
+        assert_eq!(tree.checked_max_path_sum(), Ok(Some(u32::MAX - 1)));
+
+        tree.remove_subtree(0);
+        assert_eq!(tree.checked_max_path_sum(), Ok(None));
+    }
+
+    #[test]
+    fn test_saturating() {
+        let tree = large_tree();
+        assert_eq!(tree.saturating_sum(), u32::MAX);
+        assert_eq!(tree.saturating_max_path_sum(), Some(u32::MAX));
+    }
+
+    #[test]
+    fn test_wide_accumulator() {
+        let tree = large_tree();
+        let half = (u32::MAX / 2) as u64;
+
+        assert_eq!(tree.wide_sum(), 2 * half + 10);
+        assert_eq!(tree.wide_max_path_sum(), Some(2 * half + 10));
+    }
+
+    #[test]
+    fn test_try_add_node() {
+        let mut tree = Tree::with_root(10);
+
+        assert_eq!(tree.try_add_node(0, 5, true), Ok(1));
+        assert_eq!(
+            tree.try_add_node(0, 7, true),
+            Err(TreeError::ChildAlreadySet {
+                parent_id: 0,
+                is_left: true
+            })
+        );
+        assert_eq!(
+            tree.try_add_node(3, 7, false),
+            Err(TreeError::ParentNotFound { parent_id: 3 })
+        );
+
+        // The failed insertions don't change the tree
+        assert_eq!(tree.sum(), 15);
+        assert_eq!(tree.try_add_node(0, 7, false), Ok(2));
+    }
+
+    #[test]
+    fn test_try_remove_subtree() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, true); // id 1
+        tree.add_node(1, 3, true); // id 2
+
+        assert_eq!(tree.try_remove_subtree(1), Ok(2));
+        assert_eq!(
+            tree.try_remove_subtree(2),
+            Err(TreeError::NodeNotFound { node_id: 2 })
+        );
+        assert_eq!(
+            tree.try_add_node(1, 3, true),
+            Err(TreeError::ParentNotFound { parent_id: 1 })
+        );
+        assert!(tree.try_detach(7).is_err());
+        assert_eq!(tree.try_detach(0).map(|subtree| subtree.sum()), Ok(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Parent node has the right child already set")]
+    fn test_add_node_child_already_set() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 5, false);
+        tree.add_node(0, 7, false);
+    }
 }